@@ -169,15 +169,6 @@ if (-not (Get-Command cargo -ErrorAction SilentlyContinue)) {
 $CargoVersion = (cargo --version) -replace 'cargo ', ''
 Write-Host "  Rust $CargoVersion"
 
-# Check ffmpeg
-if (-not (Get-Command ffmpeg -ErrorAction SilentlyContinue)) {
-    Write-Host "  Warning: ffmpeg not found." -ForegroundColor Yellow
-    Write-Host "  Install it: winget install ffmpeg"
-    Write-Host "  Or: scoop install ffmpeg"
-} else {
-    Write-Host "  ffmpeg found"
-}
-
 # Build transcribe-cli
 Write-Host "  Building transcribe-cli (this may take a few minutes)..."
 Push-Location "$TmpDir\vocord\transcribe-cli"
@@ -288,18 +288,6 @@ else
     fi
     echo -e "  Rust $(cargo --version | cut -d' ' -f2)"
 
-    # Check ffmpeg
-    if ! command -v ffmpeg &> /dev/null; then
-        echo -e "${YELLOW}  Warning: ffmpeg not found.${NC}"
-        if [[ "$OS" == "Darwin" ]]; then
-            echo "  Install it: brew install ffmpeg"
-        elif [[ "$OS" == "Linux" ]]; then
-            echo "  Install it: sudo apt install ffmpeg"
-        fi
-    else
-        echo -e "  ffmpeg found"
-    fi
-
     # Build transcribe-cli
     echo "  Building transcribe-cli (this may take a few minutes)..."
     cd "$TMPDIR_VOCORD/vocord/transcribe-cli"
@@ -4,10 +4,11 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-import { execFile, spawn } from "child_process";
+import { spawn } from "child_process";
 import { randomBytes } from "crypto";
-import { createWriteStream, existsSync, mkdirSync, readFileSync, readdirSync, rmSync, statSync } from "fs";
+import { existsSync, mkdirSync, readFileSync, readdirSync, rmSync, statSync, writeFileSync } from "fs";
 import https from "https";
+import { createConnection } from "net";
 import { arch, homedir, platform, tmpdir } from "os";
 import { join } from "path";
 
@@ -17,8 +18,12 @@ const VOCORD_VENV_BIN = join(VOCORD_DATA, "venv", "bin");
 const DEFAULT_WHISPER_MODEL = join(VOCORD_DATA, "ggml-large-v3-turbo.bin");
 const DEFAULT_MLX_MODEL = "mlx-community/whisper-large-v3-turbo";
 const MAX_REDIRECTS = 5;
+const MAX_AUDIO_BYTES = 25 * 1024 * 1024;
 const ALLOWED_HOSTS = ["cdn.discordapp.com", "media.discordapp.net"];
 const SUBPROCESS_TIMEOUT_MS = 5 * 60 * 1000;
+const DAEMON_SOCKET = join(VOCORD_DATA, "transcribe.sock");
+const DAEMON_START_TIMEOUT_MS = 60 * 1000;
+const CLI_NOT_FOUND = "transcribe-cli not found. Build it with: cd transcribe-cli && cargo build --release";
 
 function ensureTempDir(): void {
     if (!existsSync(TEMP_DIR)) {
@@ -62,11 +67,8 @@ function resolveBackend(): "mlx-whisper" | "transcribe-rs" {
     return isMacAppleSilicon() ? "mlx-whisper" : "transcribe-rs";
 }
 
+/** The same policy as `validate` in transcribe-cli's fetch.rs. */
 function validateAudioUrl(url: string): void {
-    if (!url.startsWith("https://")) {
-        throw new Error("Only HTTPS URLs are supported");
-    }
-
     let parsed: URL;
     try {
         parsed = new URL(url);
@@ -74,25 +76,29 @@ function validateAudioUrl(url: string): void {
         throw new Error(`Invalid URL: ${url}`);
     }
 
+    if (parsed.protocol !== "https:") {
+        throw new Error(`Only HTTPS URLs are supported: ${url}`);
+    }
+    if (parsed.username || parsed.password) {
+        throw new Error("URLs with credentials are not supported");
+    }
+    // The URL parser drops the default port, so any port left is another one.
+    if (parsed.port && parsed.port !== "443") {
+        throw new Error(`Non-standard port in URL: ${url}`);
+    }
     if (!ALLOWED_HOSTS.includes(parsed.hostname)) {
         throw new Error(`Untrusted audio host: ${parsed.hostname}`);
     }
 }
 
-async function downloadAudio(url: string, redirectCount = 0): Promise<string> {
+/**
+ * Download a voice message into memory for mlx-whisper. transcribe-cli downloads by itself (`--url`),
+ * applying the same HTTPS/allowlist/redirect policy; keep the two in sync.
+ */
+async function downloadAudio(url: string, redirectCount = 0): Promise<Buffer> {
     validateAudioUrl(url);
 
     return new Promise((resolve, reject) => {
-        ensureTempDir();
-        const filename = `audio_${Date.now()}_${randomBytes(4).toString("hex")}.ogg`;
-        const filepath = join(TEMP_DIR, filename);
-        const file = createWriteStream(filepath);
-
-        const cleanup = () => {
-            file.destroy();
-            rmSync(filepath, { force: true });
-        };
-
         https.get(url, {
             headers: {
                 "User-Agent": "Mozilla/5.0 (compatible; Vocord/1.0)"
@@ -101,7 +107,7 @@ async function downloadAudio(url: string, redirectCount = 0): Promise<string> {
             const status = response.statusCode ?? 0;
 
             if ([301, 302, 303, 307, 308].includes(status)) {
-                cleanup();
+                response.resume();
                 if (redirectCount >= MAX_REDIRECTS) {
                     reject(new Error("Too many redirects"));
                     return;
@@ -122,66 +128,46 @@ async function downloadAudio(url: string, redirectCount = 0): Promise<string> {
             }
 
             if (status !== 200) {
-                cleanup();
+                response.resume();
                 reject(new Error(`Failed to download: HTTP ${status}`));
                 return;
             }
 
-            response.pipe(file);
-
-            file.on("finish", () => {
-                file.close(err => {
-                    if (err) {
-                        cleanup();
-                        reject(err);
-                    } else {
-                        resolve(filepath);
-                    }
-                });
+            const chunks: Buffer[] = [];
+            let size = 0;
+            response.on("data", (chunk: Buffer) => {
+                size += chunk.length;
+                if (size > MAX_AUDIO_BYTES) {
+                    response.destroy();
+                    reject(new Error("Audio file is too large"));
+                    return;
+                }
+                chunks.push(chunk);
             });
-        }).on("error", err => {
-            cleanup();
-            reject(err);
-        });
+            response.on("end", () => resolve(Buffer.concat(chunks)));
+            response.on("error", reject);
+        }).on("error", reject);
     });
 }
 
-/** Convert OGG audio to WAV (16kHz, 16-bit, mono) using ffmpeg. */
-async function convertToWav(oggPath: string): Promise<string> {
-    // Always derive the WAV path by stripping any extension then appending .wav,
-    // so the output path is never the same as the input path.
-    const wavPath = oggPath.replace(/\.[^.]+$/, ".wav");
-
-    return new Promise((resolve, reject) => {
-        execFile("ffmpeg", [
-            "-i", oggPath,
-            "-ar", "16000",
-            "-ac", "1",
-            "-sample_fmt", "s16",
-            "-y",
-            wavPath
-        ], { timeout: 30000, env: getExtendedEnv() }, (error, _stdout, stderr) => {
-            rmSync(oggPath, { force: true });
-
-            if (error) {
-                rmSync(wavPath, { force: true });
-                const isMissing = stderr?.includes("not found") || error.message.includes("ENOENT");
-                const msg = isMissing
-                    ? "ffmpeg not found. Install it: brew install ffmpeg (macOS) / sudo apt install ffmpeg (Linux)"
-                    : `ffmpeg conversion failed: ${error.message}`;
-                reject(new Error(msg));
-                return;
-            }
+/** Write audio to a temp file, for backends that can only read files. */
+function writeTempAudio(audio: Buffer): string {
+    ensureTempDir();
+    const filepath = join(TEMP_DIR, `audio_${Date.now()}_${randomBytes(4).toString("hex")}.ogg`);
+    writeFileSync(filepath, audio, { mode: 0o600 });
+    return filepath;
+}
 
-            resolve(wavPath);
-        });
-    });
+/** Build an Error from a JSON error object, appending transcribe-cli's remediation hint when present. */
+function cliError(result: { error: string; hint?: string; }): Error {
+    return new Error(result.hint ? `${result.error}. ${result.hint}` : result.error);
 }
 
 interface SubprocessOptions {
     command: string;
     args: string[];
-    cleanupPath: string;
+    /** Temp file to delete once the process exits. */
+    cleanupPath?: string;
     label: string;
     /** Which stream to look for JSON error output on non-zero exit. */
     errorStream?: "stdout" | "stderr";
@@ -197,6 +183,7 @@ async function runSubprocess(options: SubprocessOptions): Promise<string> {
 
     return new Promise((resolve, reject) => {
         const proc = spawn(command, args, { env: getExtendedEnv() });
+        const cleanup = () => { if (cleanupPath) rmSync(cleanupPath, { force: true }); };
 
         let stdout = "";
         let stderr = "";
@@ -212,7 +199,7 @@ async function runSubprocess(options: SubprocessOptions): Promise<string> {
 
         proc.on("close", code => {
             clearTimeout(timeout);
-            rmSync(cleanupPath, { force: true });
+            cleanup();
 
             if (killed) {
                 reject(new Error(`${label} timed out`));
@@ -222,9 +209,11 @@ async function runSubprocess(options: SubprocessOptions): Promise<string> {
             if (code !== 0) {
                 const errorOutput = errorStream === "stderr" ? stderr : stdout;
                 try {
-                    const result = JSON.parse(errorOutput.trim());
+                    // Event lines (such as a GPU fallback) may come first;
+                    // the error is always the last line.
+                    const result = JSON.parse(errorOutput.trim().split("\n").pop() ?? "");
                     if (result.error) {
-                        reject(new Error(result.error));
+                        reject(cliError(result));
                         return;
                     }
                 } catch { /* ignore parse errors */ }
@@ -246,7 +235,7 @@ async function runSubprocess(options: SubprocessOptions): Promise<string> {
             try {
                 const result = JSON.parse(trimmed);
                 if (result.error) {
-                    reject(new Error(result.error));
+                    reject(cliError(result));
                 } else if (typeof result.text !== "string") {
                     reject(new Error(`${label} output missing 'text' field: ${trimmed}`));
                 } else {
@@ -259,7 +248,7 @@ async function runSubprocess(options: SubprocessOptions): Promise<string> {
 
         proc.on("error", err => {
             clearTimeout(timeout);
-            rmSync(cleanupPath, { force: true });
+            cleanup();
             if (enoentMessage && (err as NodeJS.ErrnoException).code === "ENOENT") {
                 reject(new Error(enoentMessage));
             } else {
@@ -270,7 +259,8 @@ async function runSubprocess(options: SubprocessOptions): Promise<string> {
 }
 
 /** Transcribe audio using mlx-whisper (macOS ARM). */
-async function runMlxWhisper(audioPath: string): Promise<string> {
+async function runMlxWhisper(audio: Buffer): Promise<string> {
+    const audioPath = writeTempAudio(audio);
     const venvPython = join(VOCORD_VENV_BIN, "python");
     const python = existsSync(venvPython) ? venvPython : "python3";
 
@@ -287,23 +277,124 @@ async function runMlxWhisper(audioPath: string): Promise<string> {
     });
 }
 
+/** Send one NDJSON request (followed by its raw audio payload, if any) to the transcribe-cli daemon and resolve with its parsed reply. */
+function sendDaemonRequest(request: object, timeoutMs: number, payload?: Buffer): Promise<any> {
+    return new Promise((resolve, reject) => {
+        const socket = createConnection(DAEMON_SOCKET);
+        let buffer = "";
+
+        socket.setTimeout(timeoutMs, () => {
+            socket.destroy();
+            reject(new Error("transcribe-cli daemon timed out"));
+        });
+
+        socket.on("connect", () => {
+            socket.write(JSON.stringify(request) + "\n");
+            if (payload) socket.write(payload);
+        });
+        socket.on("data", data => {
+            buffer += data.toString();
+            const newline = buffer.indexOf("\n");
+            if (newline === -1) return;
+            socket.end();
+            try {
+                resolve(JSON.parse(buffer.slice(0, newline)));
+            } catch {
+                reject(new Error(`Failed to parse transcribe-cli daemon output: ${buffer}`));
+            }
+        });
+        socket.on("error", reject);
+    });
+}
+
+/**
+ * Whether a daemon is listening on the socket. The daemon handles one
+ * connection at a time, so during a long transcription a ping would time out,
+ * but the connection is still accepted into the backlog. It only binds the
+ * socket once its model is loaded.
+ */
+function daemonListening(): Promise<boolean> {
+    return new Promise(resolve => {
+        const socket = createConnection(DAEMON_SOCKET);
+        socket.on("connect", () => {
+            socket.destroy();
+            resolve(true);
+        });
+        socket.on("error", () => resolve(false));
+    });
+}
+
+/**
+ * Start the transcribe-cli daemon if it is not already listening, and wait
+ * until it is ready. Fails as soon as the daemon can't be spawned or exits,
+ * e.g. on a missing or corrupt model, rather than waiting out the timeout.
+ */
+async function ensureDaemon(cliPath: string): Promise<void> {
+    if (await daemonListening()) return;
+
+    console.log("[Vocord] Starting transcribe-cli daemon...");
+    const proc = spawn(cliPath, ["serve", "--socket", DAEMON_SOCKET, "--model", DEFAULT_WHISPER_MODEL, "--cache"], {
+        detached: true,
+        stdio: "ignore",
+        env: getExtendedEnv(),
+    });
+    let failure: Error | undefined;
+    proc.on("error", err => {
+        failure = (err as NodeJS.ErrnoException).code === "ENOENT" ? new Error(CLI_NOT_FOUND) : err;
+    });
+    proc.on("exit", (code, signal) => {
+        failure ??= new Error(`transcribe-cli daemon exited before it was ready (${signal ?? `code ${code}`})`);
+    });
+    proc.unref();
+
+    const deadline = Date.now() + DAEMON_START_TIMEOUT_MS;
+    while (Date.now() < deadline) {
+        await new Promise(r => setTimeout(r, 250));
+        if (await daemonListening()) return;
+        if (failure) throw failure;
+    }
+    throw new Error("transcribe-cli daemon did not start");
+}
+
 /** Transcribe audio using transcribe-cli (cross-platform, Whisper). */
-async function runTranscribeRs(wavPath: string): Promise<string> {
+async function runTranscribeRs(audioUrl: string): Promise<string> {
     if (!existsSync(DEFAULT_WHISPER_MODEL)) {
-        rmSync(wavPath, { force: true });
         throw new Error(`Whisper model not found at ${DEFAULT_WHISPER_MODEL}. Re-run the Vocord installer.`);
     }
 
     const cliBin = platform() === "win32" ? "transcribe-cli.exe" : "transcribe-cli";
     const cliPath = join(VOCORD_DATA, cliBin);
 
+    // The daemon keeps the model loaded between messages. It needs Unix domain
+    // sockets, so Windows (and a daemon that fails to start) falls back to a
+    // one-shot run.
+    if (platform() !== "win32") {
+        let daemonReady = false;
+        try {
+            await ensureDaemon(cliPath);
+            daemonReady = true;
+        } catch (err) {
+            console.warn("[Vocord] transcribe-cli daemon unavailable, falling back to one-shot mode:", err);
+        }
+
+        if (daemonReady) {
+            // No fallback once the request is sent: after a timeout, a one-shot
+            // run would only double the wait.
+            const result = await sendDaemonRequest({ url: audioUrl, vad: true }, SUBPROCESS_TIMEOUT_MS);
+            if (result.error) throw cliError(result);
+            if (typeof result.text !== "string") {
+                throw new Error(`transcribe-cli output missing 'text' field: ${JSON.stringify(result)}`);
+            }
+            return result.text;
+        }
+    }
+
     return runSubprocess({
         command: cliPath,
-        args: ["--audio", wavPath, "--model", DEFAULT_WHISPER_MODEL],
-        cleanupPath: wavPath,
+        args: ["--url", audioUrl, "--model", DEFAULT_WHISPER_MODEL, "--vad", "--cache"],
         label: "transcribe-cli",
         errorStream: "stderr",
-        enoentMessage: "transcribe-cli not found. Build it with: cd transcribe-cli && cargo build --release",
+        enoentMessage: CLI_NOT_FOUND,
     });
 }
 
@@ -313,21 +404,17 @@ export async function transcribe(
 ): Promise<{ text?: string; error?: string }> {
     try {
         const backend = resolveBackend();
-        console.log(`[Vocord] Backend: ${backend} | Downloading audio...`);
-
-        const oggPath = await downloadAudio(audioUrl);
+        console.log(`[Vocord] Backend: ${backend}`);
 
         let text: string;
 
         if (backend === "mlx-whisper") {
-            console.log(`[Vocord] Transcribing with mlx-whisper, model: ${DEFAULT_MLX_MODEL}`);
-            text = await runMlxWhisper(oggPath);
+            console.log(`[Vocord] Downloading audio, transcribing with mlx-whisper, model: ${DEFAULT_MLX_MODEL}`);
+            text = await runMlxWhisper(await downloadAudio(audioUrl));
         } else {
-            console.log(`[Vocord] Converting OGG to WAV...`);
-            const wavPath = await convertToWav(oggPath);
-
+            // transcribe-cli downloads and validates the URL itself.
             console.log(`[Vocord] Transcribing with Whisper GGML, model: ${DEFAULT_WHISPER_MODEL}`);
-            text = await runTranscribeRs(wavPath);
+            text = await runTranscribeRs(audioUrl);
         }
 
         const preview = text.length > 50 ? `${text.substring(0, 50)}...` : text;
@@ -0,0 +1,441 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use ogg::reading::PacketReader;
+use opus::{Channels, Decoder};
+use serde::Serialize;
+
+use crate::error::{CliError, ErrorCode, ResultExt};
+
+/// Sample rate expected by the transcription engine.
+pub const SAMPLE_RATE: u32 = 16000;
+
+/// Opus always runs at 48 kHz internally; granule positions and the pre-skip
+/// in the OpusHead header are expressed in 48 kHz samples.
+const OPUS_INTERNAL_RATE: u32 = 48000;
+
+/// Largest Opus frame (120 ms) at the engine sample rate, per channel.
+const MAX_OPUS_FRAME: usize = (SAMPLE_RATE as usize) * 120 / 1000;
+
+/// WAV sample rates accepted for resampling.
+const MIN_WAV_RATE: u32 = 8000;
+const MAX_WAV_RATE: u32 = 96000;
+
+/// Zero crossings of the resampling filter on each side of a sample. More is
+/// a sharper low-pass at the cost of speed; 16 is plenty for speech.
+const RESAMPLE_ZEROS: f64 = 16.0;
+
+/// Decoded engine samples plus the shape of the input they came from.
+pub struct Decoded {
+    pub samples: Vec<f32>,
+    pub format: SourceFormat,
+}
+
+/// The input's original format, before downmixing and resampling.
+#[derive(Serialize, Clone)]
+pub struct SourceFormat {
+    pub container: &'static str,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Not meaningful for Opus, which has no fixed bit depth.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bits_per_sample: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_format: Option<&'static str>,
+}
+
+/// Decode an audio file into 16 kHz mono f32 samples.
+pub fn load(path: &Path) -> Result<Decoded, Box<dyn std::error::Error>> {
+    let file = File::open(path).code(ErrorCode::AudioNotFound)?;
+    decode(BufReader::new(file), &path.display().to_string())
+}
+
+/// Decode an in-memory audio file (e.g. an HTTP upload).
+pub fn decode_bytes(bytes: &[u8], source: &str) -> Result<Decoded, Box<dyn std::error::Error>> {
+    decode(Cursor::new(bytes), source)
+}
+
+/// Decode audio into 16 kHz mono f32 samples, detecting the container from
+/// its magic bytes rather than trusting a file extension. `source` names the
+/// input in error messages.
+fn decode<R: Read + Seek>(
+    mut reader: R,
+    source: &str,
+) -> Result<Decoded, Box<dyn std::error::Error>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).map_err(|_| {
+        CliError::new(
+            ErrorCode::AudioDecodeFailed,
+            format!("Audio file is too short: {}", source),
+        )
+    })?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let result = match &magic {
+        b"RIFF" => decode_wav(reader),
+        b"OggS" => decode_ogg_opus(reader),
+        _ => Err(CliError::new(
+            ErrorCode::UnsupportedAudioFormat,
+            format!(
+                "Unsupported audio format: {} (expected WAV or Ogg/Opus)",
+                source
+            ),
+        )
+        .into()),
+    };
+
+    // Format problems are tagged where they are detected; anything else
+    // coming out of the container or codec libraries means corrupt data.
+    result.map_err(|e| {
+        if e.is::<CliError>() {
+            e
+        } else {
+            CliError::new(
+                ErrorCode::AudioDecodeFailed,
+                format!("Failed to decode {}: {}", source, e),
+            )
+            .into()
+        }
+    })
+}
+
+/// Decode a WAV file of any common shape: 8-96 kHz, 8/16/24/32-bit integer
+/// or 32-bit float, any channel count. Channels are averaged to mono and the
+/// result resampled to 16 kHz.
+fn decode_wav<R: Read>(reader: R) -> Result<Decoded, Box<dyn std::error::Error>> {
+    let mut wav = hound::WavReader::new(reader)?;
+    let spec = wav.spec();
+
+    let supported_depth = match spec.sample_format {
+        hound::SampleFormat::Int => matches!(spec.bits_per_sample, 8 | 16 | 24 | 32),
+        hound::SampleFormat::Float => spec.bits_per_sample == 32,
+    };
+    if !supported_depth
+        || !(MIN_WAV_RATE..=MAX_WAV_RATE).contains(&spec.sample_rate)
+        || spec.channels == 0
+    {
+        return Err(CliError::new(
+            ErrorCode::UnsupportedAudioFormat,
+            format!(
+                "Unsupported WAV format: {} Hz, {} channel(s), {}-bit {} \
+                 (expected 8-96 kHz, 8/16/24/32-bit integer or 32-bit float)",
+                spec.sample_rate,
+                spec.channels,
+                spec.bits_per_sample,
+                sample_format_name(spec.sample_format)
+            ),
+        )
+        .into());
+    }
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            wav.samples::<i32>()
+                .map(|s| Ok(s? as f32 / scale))
+                .collect::<Result<_, hound::Error>>()?
+        }
+        hound::SampleFormat::Float => wav.samples::<f32>().collect::<Result<_, _>>()?,
+    };
+
+    let mono = downmix(&interleaved, spec.channels as usize);
+    Ok(Decoded {
+        samples: resample(&mono, spec.sample_rate, SAMPLE_RATE),
+        format: SourceFormat {
+            container: "wav",
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            bits_per_sample: Some(spec.bits_per_sample),
+            sample_format: Some(sample_format_name(spec.sample_format)),
+        },
+    })
+}
+
+fn sample_format_name(format: hound::SampleFormat) -> &'static str {
+    match format {
+        hound::SampleFormat::Int => "int",
+        hound::SampleFormat::Float => "float",
+    }
+}
+
+/// Average interleaved channels into one.
+fn downmix(interleaved: &[f32], channels: usize) -> Vec<f32> {
+    if channels == 1 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Band-limited resampling with a Blackman-windowed sinc filter. When
+/// downsampling, the cutoff drops to the output Nyquist frequency so content
+/// above it is filtered out rather than aliased into the speech band.
+///
+/// Output samples only ever fall on `to / gcd(from, to)` distinct phases
+/// between two input samples, so the filter taps for each phase are computed
+/// once up front and each output sample is a plain dot product.
+fn resample(input: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || input.is_empty() {
+        return input.to_vec();
+    }
+
+    let divisor = gcd(from, to);
+    let (up, down) = ((to / divisor) as usize, (from / divisor) as usize);
+    let ratio = to as f64 / from as f64;
+    let cutoff = ratio.min(1.0);
+    // Filter half-width in input samples.
+    let half_width = RESAMPLE_ZEROS / cutoff;
+    let first_tap = -(half_width.floor() as isize);
+    let taps = (half_width.ceil() as isize - first_tap + 1) as usize;
+
+    // Row `phase` weighs input samples `first_tap..` around an output sample
+    // that lies `phase / up` of the way from one input sample to the next.
+    let filters: Vec<f32> = (0..up)
+        .flat_map(|phase| {
+            let frac = phase as f64 / up as f64;
+            (0..taps).map(move |k| {
+                let t = (first_tap + k as isize) as f64 - frac;
+                (cutoff * sinc(t * cutoff) * blackman(t / half_width)) as f32
+            })
+        })
+        .collect();
+
+    let out_len = (input.len() as f64 * ratio).round() as usize;
+    (0..out_len)
+        .map(|i| {
+            let position = i * down;
+            let (base, phase) = (position / up, position % up);
+            let filter = &filters[phase * taps..(phase + 1) * taps];
+            // Clip the filter to the input at either end.
+            let start = base as isize + first_tap;
+            let lo = start.max(0) as usize;
+            let hi = (start + taps as isize).clamp(0, input.len() as isize) as usize;
+            if lo >= hi {
+                return 0.0;
+            }
+            input[lo..hi]
+                .iter()
+                .zip(&filter[(lo as isize - start) as usize..])
+                .map(|(x, w)| x * w)
+                .sum()
+        })
+        .collect()
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let px = std::f64::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+/// Blackman window over `x` in [-1, 1].
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let phase = std::f64::consts::PI * (x + 1.0);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos()
+}
+
+/// Decode an Ogg/Opus stream such as a Discord voice message. libopus handles
+/// the downmix to mono and the resampling to 16 kHz itself.
+fn decode_ogg_opus<R: Read + Seek>(reader: R) -> Result<Decoded, Box<dyn std::error::Error>> {
+    let mut packets = PacketReader::new(reader);
+
+    let head = packets.read_packet()?.ok_or("Ogg stream is empty")?;
+    let (pre_skip, format) = parse_opus_head(&head.data)?;
+
+    // The second packet carries OpusTags (comments), which we don't need.
+    packets
+        .read_packet()?
+        .ok_or("Ogg stream ends before the OpusTags header")?;
+
+    let mut decoder = Decoder::new(SAMPLE_RATE, Channels::Mono)?;
+    let mut frame = vec![0f32; MAX_OPUS_FRAME];
+    let mut samples = Vec::new();
+
+    while let Some(packet) = packets.read_packet()? {
+        let n = decoder.decode_float(&packet.data, &mut frame, false)?;
+        samples.extend_from_slice(&frame[..n]);
+    }
+
+    let skip = (pre_skip as usize * SAMPLE_RATE as usize) / OPUS_INTERNAL_RATE as usize;
+    samples.drain(..skip.min(samples.len()));
+    Ok(Decoded { samples, format })
+}
+
+/// Validate the OpusHead identification header and return its pre-skip and
+/// the original input format it records.
+fn parse_opus_head(data: &[u8]) -> Result<(u16, SourceFormat), Box<dyn std::error::Error>> {
+    if data.len() < 19 || &data[..8] != b"OpusHead" {
+        return Err(CliError::new(
+            ErrorCode::UnsupportedAudioFormat,
+            "Unsupported Ogg stream: only Opus audio is supported",
+        )
+        .into());
+    }
+
+    let channels = data[9];
+    let mapping_family = data[18];
+    if mapping_family != 0 && !(mapping_family == 1 && channels <= 2) {
+        return Err(CliError::new(
+            ErrorCode::UnsupportedAudioFormat,
+            format!(
+                "Unsupported Opus channel mapping (family {}, {} channels)",
+                mapping_family, channels
+            ),
+        )
+        .into());
+    }
+
+    let pre_skip = u16::from_le_bytes([data[10], data[11]]);
+    // The encoder's input rate; 0 means unspecified, and Opus itself is 48 kHz.
+    let input_rate = u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+    let format = SourceFormat {
+        container: "ogg_opus",
+        sample_rate: if input_rate == 0 {
+            OPUS_INTERNAL_RATE
+        } else {
+            input_rate
+        },
+        channels: channels as u16,
+        bits_per_sample: None,
+        sample_format: None,
+    };
+    Ok((pre_skip, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An OpusHead packet: version 1, a 312 sample pre-skip, 48 kHz input and
+    /// no output gain.
+    fn opus_head(channels: u8, mapping_family: u8) -> Vec<u8> {
+        let mut head = b"OpusHead".to_vec();
+        head.push(1);
+        head.push(channels);
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&48000u32.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes());
+        head.push(mapping_family);
+        head
+    }
+
+    #[test]
+    fn reads_pre_skip_and_format() {
+        let (pre_skip, format) = parse_opus_head(&opus_head(1, 0)).unwrap();
+        assert_eq!(pre_skip, 312);
+        assert_eq!(format.sample_rate, 48000);
+        assert_eq!(format.channels, 1);
+        let (pre_skip, format) = parse_opus_head(&opus_head(2, 1)).unwrap();
+        assert_eq!(pre_skip, 312);
+        assert_eq!(format.channels, 2);
+    }
+
+    #[test]
+    fn rejects_other_packets() {
+        let mut tags = opus_head(1, 0);
+        tags[..8].copy_from_slice(b"OpusTags");
+        assert!(parse_opus_head(&tags).is_err());
+        assert!(parse_opus_head(&opus_head(1, 0)[..18]).is_err());
+    }
+
+    #[test]
+    fn rejects_multichannel_mappings() {
+        assert!(parse_opus_head(&opus_head(6, 1)).is_err());
+        assert!(parse_opus_head(&opus_head(2, 255)).is_err());
+    }
+
+    fn sine(frequency: f32, rate: u32, seconds: f32) -> Vec<f32> {
+        (0..(rate as f32 * seconds) as usize)
+            .map(|i| (i as f32 * frequency * std::f32::consts::TAU / rate as f32).sin())
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn wav(bits: u16, sample_format: hound::SampleFormat, samples: &[i32]) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: bits,
+            sample_format,
+        };
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn downmix_averages_channels() {
+        assert_eq!(downmix(&[0.25, -0.5], 1), [0.25, -0.5]);
+        assert_eq!(downmix(&[1.0, 0.0, 0.5, -0.5], 2), [0.5, 0.0]);
+        assert_eq!(downmix(&[0.3, 0.3, 0.3, 1.0], 3), [0.3]);
+    }
+
+    #[test]
+    fn resample_output_length() {
+        assert_eq!(resample(&vec![0.0; 44100], 44100, 16000).len(), 16000);
+        assert_eq!(resample(&vec![0.0; 8000], 8000, 16000).len(), 16000);
+        assert_eq!(resample(&[0.5; 100], 16000, 16000), [0.5; 100]);
+    }
+
+    #[test]
+    fn resample_preserves_sine_frequency() {
+        for from in [8000, 44100, 48000] {
+            let output = resample(&sine(1000.0, from, 0.5), from, SAMPLE_RATE);
+            // Skip the filter's ramp at either end.
+            let middle = &output[800..7200];
+            let crossings = middle
+                .windows(2)
+                .filter(|w| (w[0] < 0.0) != (w[1] < 0.0))
+                .count();
+            // 1 kHz over 0.4 s crosses zero 800 times.
+            assert!(
+                (798..=802).contains(&crossings),
+                "{} Hz: {}",
+                from,
+                crossings
+            );
+            assert!((rms(middle) - 0.707).abs() < 0.02, "{} Hz", from);
+        }
+    }
+
+    #[test]
+    fn resample_filters_above_output_nyquist() {
+        let output = resample(&sine(12000.0, 48000, 0.5), 48000, SAMPLE_RATE);
+        assert!(rms(&output[800..7200]) < 0.01);
+    }
+
+    #[test]
+    fn scales_integers_by_bit_depth() {
+        for bits in [8, 16, 24, 32] {
+            let full = 1i64 << (bits - 1);
+            let samples = [-full as i32, (full / 2) as i32, 0];
+            let bytes = wav(bits, hound::SampleFormat::Int, &samples);
+            let decoded = decode_bytes(&bytes, "test.wav").unwrap();
+            assert_eq!(decoded.samples, [-1.0, 0.5, 0.0], "{}-bit", bits);
+            assert_eq!(decoded.format.bits_per_sample, Some(bits));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::{CliError, ErrorCode, ResultExt};
+use crate::transcribe::{self, ErrorOutput, SuccessOutput, TranscribeOptions, Transcriber};
+
+/// Extensions picked up when an `--audio` argument names a directory.
+const AUDIO_EXTENSIONS: &[&str] = &["ogg", "oga", "opus", "wav"];
+
+/// One NDJSON line of batch output: the input path plus either the
+/// transcription or the error for that file.
+#[derive(Serialize)]
+struct BatchLine<'a> {
+    path: &'a Path,
+    #[serde(flatten)]
+    result: BatchResult,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchResult {
+    Ok(SuccessOutput),
+    Err(ErrorOutput),
+}
+
+fn is_glob(arg: &Path) -> bool {
+    arg.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Whether the `--audio` arguments describe a batch rather than the classic
+/// single-file invocation, whose `{"text": ...}` contract must not change.
+pub fn is_batch(args: &[PathBuf]) -> bool {
+    args.len() > 1 || args.iter().any(|a| a.is_dir() || is_glob(a))
+}
+
+/// Expand directories (non-recursively, audio extensions only) and glob
+/// patterns into a sorted file list. Plain paths are kept as-is, even if they
+/// don't exist, so they are reported as per-file errors.
+pub fn expand_inputs(args: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+
+    for arg in args {
+        if arg.as_os_str() == "-" {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                "--audio - reads a single file from stdin and cannot be combined with other inputs",
+            )
+            .into());
+        }
+        if arg.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(arg)
+                .code(ErrorCode::AudioNotFound)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.is_file() && has_audio_extension(p))
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else if is_glob(arg) {
+            let pattern = arg.to_string_lossy();
+            let mut matches: Vec<PathBuf> = glob::glob(&pattern)
+                .map_err(|e| {
+                    CliError::new(
+                        ErrorCode::InvalidArgument,
+                        format!("Invalid glob pattern {}: {}", pattern, e),
+                    )
+                })?
+                .filter_map(Result::ok)
+                .filter(|p| p.is_file())
+                .collect();
+            matches.sort();
+            files.extend(matches);
+        } else {
+            files.push(arg.clone());
+        }
+    }
+
+    if files.is_empty() {
+        return Err(CliError::new(
+            ErrorCode::AudioNotFound,
+            "No audio files matched the --audio arguments",
+        )
+        .into());
+    }
+    Ok(files)
+}
+
+fn has_audio_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Transcribe every file with the same transcriber, so the model is loaded
+/// at most once, writing one NDJSON line per file as soon as it is done. A
+/// failing file is reported on its own line and does not stop the batch; a
+/// model that fails to load does, since every file would fail the same way.
+pub fn run(
+    transcriber: &mut Transcriber,
+    files: &[PathBuf],
+    options: &TranscribeOptions,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // With the cache on, a batch of cache hits never needs the model, so it
+    // is loaded on the first miss instead.
+    if !transcriber.has_cache() {
+        transcriber.engine()?;
+    }
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    for path in files {
+        let result = match transcribe_one(transcriber, path, options, verbose) {
+            Ok(output) => BatchResult::Ok(output),
+            Err(e) if is_model_error(e.as_ref()) => return Err(e),
+            Err(e) => BatchResult::Err(ErrorOutput::new(e.as_ref())),
+        };
+        let line = BatchLine { path, result };
+        writeln!(out, "{}", serde_json::to_string(&line)?)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn is_model_error(error: &(dyn std::error::Error + 'static)) -> bool {
+    error.downcast_ref::<CliError>().is_some_and(|e| {
+        matches!(
+            e.code,
+            ErrorCode::ModelNotFound | ErrorCode::ModelLoadFailed | ErrorCode::ModelCorrupt
+        )
+    })
+}
+
+fn transcribe_one(
+    transcriber: &mut Transcriber,
+    path: &Path,
+    options: &TranscribeOptions,
+    verbose: bool,
+) -> Result<SuccessOutput, Box<dyn std::error::Error>> {
+    let decoded = transcribe::load_audio(path)?;
+    let mut output = transcriber.transcribe(decoded.samples, options)?;
+    if verbose {
+        output.audio = Some(decoded.format);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::cache::Cache;
+    use crate::engine::EngineKind;
+    use crate::transcribe::{Device, ModelArgs};
+
+    /// A fresh directory holding empty files with the given names.
+    fn dir_with(name: &str, files: &[&str]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("transcribe-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested.ogg")).unwrap();
+        for file in files {
+            fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    fn model(path: PathBuf) -> ModelArgs {
+        ModelArgs {
+            model: Some(path),
+            engine: EngineKind::Whisper,
+            device: Device::Cpu,
+            verify_model: false,
+        }
+    }
+
+    fn error_code(error: &(dyn std::error::Error + 'static)) -> Option<ErrorCode> {
+        error.downcast_ref::<CliError>().map(|e| e.code)
+    }
+
+    /// One second of silence as a 16 kHz WAV file.
+    fn write_wav(path: &Path) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..16000 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn missing_model_aborts_before_any_file() {
+        let mut transcriber = Transcriber::new(model("/nonexistent/ggml-base.bin".into()), None);
+        let files = [PathBuf::from("/nonexistent/a.ogg")];
+        let options = TranscribeOptions::default();
+        let err = run(&mut transcriber, &files, &options, false).unwrap_err();
+        assert_eq!(error_code(err.as_ref()), Some(ErrorCode::ModelNotFound));
+    }
+
+    #[test]
+    fn missing_model_aborts_on_first_cache_miss() {
+        let dir = dir_with("model-miss", &[]);
+        let audio = dir.join("a.wav");
+        write_wav(&audio);
+        let cache = Cache::new(dir.join("cache"), 1024 * 1024);
+        let mut transcriber = Transcriber::new(model(dir.join("ggml-base.bin")), Some(cache));
+        let files = [audio.clone(), audio];
+        let options = TranscribeOptions::default();
+        let err = run(&mut transcriber, &files, &options, false).unwrap_err();
+        assert_eq!(error_code(err.as_ref()), Some(ErrorCode::ModelNotFound));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn single_file_is_not_a_batch() {
+        assert!(!is_batch(&[PathBuf::from("memo.ogg")]));
+        assert!(is_batch(&[PathBuf::from("a.ogg"), PathBuf::from("b.ogg")]));
+        assert!(is_batch(&[PathBuf::from("exports/*.ogg")]));
+        assert!(is_batch(&[std::env::temp_dir()]));
+    }
+
+    #[test]
+    fn expands_directory_to_sorted_audio_files() {
+        let dir = dir_with("expand-dir", &["b.ogg", "a.WAV", "c.opus", "notes.txt"]);
+        let files = expand_inputs(std::slice::from_ref(&dir)).unwrap();
+        assert_eq!(
+            files,
+            [dir.join("a.WAV"), dir.join("b.ogg"), dir.join("c.opus")]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn expands_globs_and_keeps_plain_paths() {
+        let dir = dir_with("expand-glob", &["b.ogg", "a.ogg", "a.wav"]);
+        let missing = dir.join("missing.ogg");
+        let files = expand_inputs(&[dir.join("*.ogg"), missing.clone()]).unwrap();
+        assert_eq!(files, [dir.join("a.ogg"), dir.join("b.ogg"), missing]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_inputs_matching_nothing() {
+        let dir = dir_with("expand-empty", &["notes.txt"]);
+        assert!(expand_inputs(std::slice::from_ref(&dir)).is_err());
+        assert!(expand_inputs(&[dir.join("*.ogg")]).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{CliError, ErrorCode};
+use crate::transcribe::{SuccessOutput, TranscribeOptions};
+
+#[derive(clap::Args, Clone)]
+pub struct CacheArgs {
+    /// Reuse transcripts of audio already transcribed with the same model
+    /// and options, from an on-disk cache
+    #[arg(long)]
+    cache: bool,
+
+    /// Cache directory (default: the user cache dir, e.g. ~/.cache/vocord/transcripts)
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// Maximum cache size in megabytes; least recently used entries are evicted
+    #[arg(long, default_value_t = 100)]
+    cache_max_mb: u64,
+}
+
+impl CacheArgs {
+    pub fn open(&self) -> Option<Cache> {
+        self.cache.then(|| {
+            Cache::new(
+                self.cache_dir.clone().unwrap_or_else(default_dir),
+                self.cache_max_mb * 1024 * 1024,
+            )
+        })
+    }
+}
+
+/// Platform cache directory, without pulling in a dependency for it.
+pub fn default_dir() -> PathBuf {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library").join("Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+    };
+    base.unwrap_or_else(std::env::temp_dir)
+        .join("vocord")
+        .join("transcripts")
+}
+
+/// Content-addressed transcript store: one JSON file per entry, named after
+/// the SHA-256 of the decoded audio, the model identity and the options.
+/// Keying on decoded samples rather than file bytes means re-encoded or
+/// forwarded copies of the same voice message still hit.
+pub struct Cache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// Compute the cache key for a transcription request.
+    ///
+    /// The model is identified by its path, size and modification time:
+    /// hashing an ~800 MB file on every request would cost more than the
+    /// cache saves, and a re-downloaded model changes the mtime anyway.
+    pub fn key(
+        samples: &[f32],
+        model: &Path,
+        options: &TranscribeOptions,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let metadata = fs::metadata(model).map_err(|_| {
+            CliError::new(
+                ErrorCode::ModelNotFound,
+                format!("Model file not found: {}", model.display()),
+            )
+        })?;
+        let modified = metadata
+            .modified()?
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let model_path = fs::canonicalize(model).unwrap_or_else(|_| model.to_path_buf());
+
+        let mut hasher = Sha256::new();
+        for sample in samples {
+            hasher.update(sample.to_le_bytes());
+        }
+        hasher.update(model_path.to_string_lossy().as_bytes());
+        hasher.update(metadata.len().to_le_bytes());
+        hasher.update(modified.to_le_bytes());
+        hasher.update(serde_json::to_vec(options)?);
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Look up an entry, marking it as recently used on a hit.
+    pub fn get(&self, key: &str) -> Option<SuccessOutput> {
+        let path = self.entry_path(key);
+        let data = fs::read(&path).ok()?;
+        let output = serde_json::from_slice(&data).ok()?;
+        // The mtime doubles as the LRU timestamp.
+        if let Ok(file) = File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(output)
+    }
+
+    /// Store an entry, then evict least recently used entries over the cap.
+    pub fn put(&self, key: &str, output: &SuccessOutput) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        // Write to a temporary name and rename, so a concurrent reader never
+        // sees a half-written entry.
+        let path = self.entry_path(key);
+        let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+        fs::write(&tmp, serde_json::to_vec(output)?)?;
+        fs::rename(&tmp, &path)?;
+
+        self.evict()
+    }
+
+    fn entries(&self) -> io::Result<Vec<(PathBuf, fs::Metadata)>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                entries.push((path, entry.metadata()?));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|(_, m)| m.len()).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+
+        entries.sort_by_key(|(_, m)| m.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+        for (path, metadata) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(&path)?;
+            total -= metadata.len();
+        }
+        Ok(())
+    }
+
+    /// Delete every entry and return how many were removed.
+    pub fn clear(&self) -> io::Result<usize> {
+        let entries = match self.entries() {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        for (path, _) in &entries {
+            fs::remove_file(path)?;
+        }
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("transcribe-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn key_depends_on_audio_and_options() {
+        let dir = temp_dir("cache-key");
+        let model = dir.join("model.bin");
+        fs::write(&model, b"model").unwrap();
+        let options = TranscribeOptions::default();
+        let german = TranscribeOptions {
+            language: "de".to_string(),
+            ..Default::default()
+        };
+
+        let key = Cache::key(&[0.0, 0.5], &model, &options).unwrap();
+        assert_eq!(key, Cache::key(&[0.0, 0.5], &model, &options).unwrap());
+        assert_ne!(key, Cache::key(&[0.0, 0.25], &model, &options).unwrap());
+        assert_ne!(key, Cache::key(&[0.0, 0.5], &model, &german).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let dir = temp_dir("cache-evict");
+        let now = SystemTime::now();
+        for (i, name) in ["old", "middle", "new"].iter().enumerate() {
+            let path = dir.join(format!("{}.json", name));
+            fs::write(&path, [b' '; 10]).unwrap();
+            let age = Duration::from_secs(60 * (3 - i as u64));
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - age)
+                .unwrap();
+        }
+
+        Cache::new(dir.clone(), 20).evict().unwrap();
+        assert!(!dir.join("old.json").exists());
+        assert!(dir.join("middle.json").exists());
+        assert!(dir.join("new.json").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::engine::EngineKind;
+use crate::error::{CliError, ErrorCode};
+use crate::transcribe::{self, Device, ModelArgs};
+use crate::{audio, fetch, vad};
+
+#[derive(clap::Args)]
+pub struct DetectArgs {
+    /// Path to the audio file, or "-" to read it from stdin
+    #[arg(long, required_unless_present = "url")]
+    audio: Option<PathBuf>,
+
+    /// Download the audio from a Discord CDN URL (HTTPS only) instead of
+    /// reading a file
+    #[arg(long, conflicts_with = "audio")]
+    url: Option<String>,
+
+    // Language identification needs a multilingual Whisper model.
+    #[command(flatten)]
+    model: ModelArgs,
+
+    /// Number of languages to report, most likely first
+    #[arg(long, default_value_t = 5)]
+    top: usize,
+
+    /// Identify the language from the first 30 seconds of speech rather
+    /// than of the audio, skipping leading silence
+    #[arg(long)]
+    vad: bool,
+}
+
+#[derive(Serialize)]
+struct DetectedLanguage {
+    language: &'static str,
+    probability: f32,
+}
+
+#[derive(Serialize)]
+struct DetectOutput {
+    /// The most likely language, absent when there was no speech.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'static str>,
+    languages: Vec<DetectedLanguage>,
+    /// The device the model ran on; absent when it wasn't needed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device: Option<Device>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    no_speech: bool,
+}
+
+pub fn run(args: DetectArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.model.engine != EngineKind::Whisper {
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            "Language identification needs --engine whisper",
+        )
+        .into());
+    }
+    if args.top == 0 {
+        return Err(CliError::new(ErrorCode::InvalidArgument, "--top must be at least 1").into());
+    }
+
+    let decoded = match (&args.url, &args.audio) {
+        (Some(url), _) => audio::decode_bytes(&fetch::fetch(url)?, url)?,
+        (None, Some(path)) => transcribe::load_audio(path)?,
+        (None, None) => unreachable!("clap requires --audio or --url"),
+    };
+    let mut samples = decoded.samples;
+    if args.vad {
+        let regions = vad::detect(&samples);
+        if regions.is_empty() {
+            let output = DetectOutput {
+                language: None,
+                languages: Vec::new(),
+                device: None,
+                no_speech: true,
+            };
+            println!("{}", serde_json::to_string(&output)?);
+            return Ok(());
+        }
+        samples = vad::compact(&samples, &regions).samples;
+    }
+
+    let mut engine = args.model.load()?;
+    let mut ranked = engine.backend.detect_language(&samples)?;
+    ranked.truncate(args.top);
+    let output = DetectOutput {
+        language: ranked.first().map(|&(language, _)| language),
+        languages: ranked
+            .into_iter()
+            .map(|(language, probability)| DetectedLanguage {
+                language,
+                probability,
+            })
+            .collect(),
+        device: Some(engine.device),
+        no_speech: false,
+    };
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(())
+}
@@ -0,0 +1,328 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "parakeet", feature = "moonshine"))]
+use transcribe_rs::{TranscriptionEngine, TranscriptionResult};
+
+#[cfg(feature = "moonshine")]
+use transcribe_rs::engines::moonshine::{MoonshineEngine, MoonshineModelParams};
+#[cfg(feature = "parakeet")]
+use transcribe_rs::engines::parakeet::{
+    ParakeetEngine, ParakeetInferenceParams, ParakeetModelParams, TimestampGranularity,
+};
+
+use crate::error::{CliError, ErrorCode, ResultExt};
+use crate::transcribe::{Device, Segment, Task, TranscribeOptions};
+use crate::whisper::WhisperModel;
+use crate::{languages, progress};
+
+/// Which transcribe-rs engine runs the model. Every variant is always
+/// accepted on the command line so a build without the engine can say how
+/// to get it, rather than clap rejecting the value.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineKind {
+    /// whisper.cpp with a GGML model file: multilingual, translation, GPU
+    Whisper,
+    /// NVIDIA Parakeet ONNX model directory: English only, fast on the CPU
+    Parakeet,
+    /// Moonshine ONNX model directory: English only, the lightest option
+    Moonshine,
+}
+
+impl EngineKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EngineKind::Whisper => "whisper",
+            EngineKind::Parakeet => "parakeet",
+            EngineKind::Moonshine => "moonshine",
+        }
+    }
+
+    fn is_built(self) -> bool {
+        match self {
+            EngineKind::Whisper => true,
+            EngineKind::Parakeet => cfg!(feature = "parakeet"),
+            EngineKind::Moonshine => cfg!(feature = "moonshine"),
+        }
+    }
+}
+
+/// Validate the options against what the engine supports, before any audio
+/// is decoded or model loaded.
+pub fn check_options(
+    kind: EngineKind,
+    options: &TranscribeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !kind.is_built() {
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "This build does not include the {0} engine: rebuild with `cargo build --release --features {0}`",
+                kind.name()
+            ),
+        )
+        .into());
+    }
+
+    if options.mark_below.is_some_and(|t| !(t > 0.0 && t <= 1.0)) {
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            "--mark-below must be a confidence between 0 and 1",
+        )
+        .into());
+    }
+    if !options.marker.contains("{}") {
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            "--marker must contain {} where the word goes",
+        )
+        .into());
+    }
+
+    let language = languages::resolve(&options.language).code(ErrorCode::InvalidArgument)?;
+    if kind != EngineKind::Whisper {
+        if language.as_deref().is_some_and(|l| l != "en") {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "The {} engine only transcribes English: use --engine whisper for --language {}",
+                    kind.name(),
+                    options.language
+                ),
+            )
+            .into());
+        }
+        if options.language_probabilities.is_some() {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "The {} engine cannot detect languages: use --engine whisper for --language-probabilities",
+                    kind.name()
+                ),
+            )
+            .into());
+        }
+        if options.needs_words() {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "The {} engine has no word timestamps or confidences: use --engine whisper for --words and --mark-below",
+                    kind.name()
+                ),
+            )
+            .into());
+        }
+        if options.task != Task::Transcribe {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "The {} engine cannot translate: use --engine whisper for --task translate or both",
+                    kind.name()
+                ),
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// The result of one decoding pass, with times in seconds from the start of
+/// the samples it was given.
+pub struct Pass {
+    pub text: String,
+    pub segments: Vec<Segment>,
+    /// The language code of the speech, when the engine reports it.
+    pub language: Option<String>,
+}
+
+/// Whisper, or one of the transcribe-rs engines. The `TranscriptionEngine`
+/// trait has per-engine parameter types, so it can't be a trait object; the
+/// enum dispatches to the generic helpers below instead.
+pub enum Backend {
+    Whisper(WhisperModel),
+    #[cfg(feature = "parakeet")]
+    Parakeet(ParakeetEngine),
+    #[cfg(feature = "moonshine")]
+    Moonshine(MoonshineEngine),
+}
+
+#[cfg(any(feature = "parakeet", feature = "moonshine"))]
+fn load<E: TranscriptionEngine>(
+    mut engine: E,
+    model: &Path,
+    params: E::ModelParams,
+) -> Result<E, Box<dyn std::error::Error>> {
+    engine
+        .load_model_with_params(model, params)
+        .code(ErrorCode::ModelLoadFailed)?;
+    Ok(engine)
+}
+
+#[cfg(any(feature = "parakeet", feature = "moonshine"))]
+fn run<E: TranscriptionEngine>(
+    engine: &mut E,
+    samples: Vec<f32>,
+    params: Option<E::InferenceParams>,
+    on_segment: Option<&mut dyn FnMut(&Segment)>,
+) -> Result<Pass, Box<dyn std::error::Error>> {
+    let result: TranscriptionResult = engine
+        .transcribe_samples(samples, params)
+        .code(ErrorCode::InferenceFailed)?;
+    let segments = result
+        .segments
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| Segment {
+            start: s.start,
+            end: s.end,
+            text: s.text.trim().to_string(),
+            words: Vec::new(),
+        })
+        .collect::<Vec<_>>();
+    if let Some(on_segment) = on_segment {
+        segments.iter().for_each(on_segment);
+    }
+    Ok(Pass {
+        text: result.text,
+        segments,
+        // The other engines only transcribe English.
+        language: Some("en".to_string()),
+    })
+}
+
+impl Backend {
+    /// Load the model on the requested device, returning the device it
+    /// actually ended up on. Only Whisper can use the GPU.
+    pub fn load(
+        kind: EngineKind,
+        model: &Path,
+        device: Device,
+    ) -> Result<(Self, Device), Box<dyn std::error::Error>> {
+        if kind != EngineKind::Whisper && device == Device::Gpu {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "The {} engine runs on the CPU only; use --device cpu or auto",
+                    kind.name()
+                ),
+            )
+            .into());
+        }
+
+        match kind {
+            EngineKind::Whisper => {
+                let load_whisper = |use_gpu: bool| {
+                    WhisperModel::load(model, use_gpu)
+                        .code(ErrorCode::ModelLoadFailed)
+                        .map(Backend::Whisper)
+                };
+                match device {
+                    Device::Cpu => Ok((load_whisper(false)?, Device::Cpu)),
+                    Device::Gpu => Ok((load_whisper(true)?, Device::Gpu)),
+                    // A missing or broken GPU driver (typically Vulkan on
+                    // Linux) makes the GPU load fail; the CPU path works
+                    // everywhere, just slower.
+                    Device::Auto => match load_whisper(true) {
+                        Ok(backend) => Ok((backend, Device::Gpu)),
+                        Err(e) => {
+                            progress::gpu_fallback(&e);
+                            Ok((load_whisper(false)?, Device::Cpu))
+                        }
+                    },
+                }
+            }
+            #[cfg(feature = "parakeet")]
+            EngineKind::Parakeet => {
+                let engine = load(ParakeetEngine::new(), model, ParakeetModelParams::int8())?;
+                Ok((Backend::Parakeet(engine), Device::Cpu))
+            }
+            #[cfg(feature = "moonshine")]
+            EngineKind::Moonshine => {
+                let engine = load(
+                    MoonshineEngine::new(),
+                    model,
+                    MoonshineModelParams::default(),
+                )?;
+                Ok((Backend::Moonshine(engine), Device::Cpu))
+            }
+            // Rejected by `check_options` before a model is ever loaded.
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("{} engine not built", kind.name()),
+        }
+    }
+
+    /// Run one decoding pass. `translate` asks Whisper for English output;
+    /// the other engines only ever produce English. `on_segment` sees each
+    /// segment as it is decoded; only Whisper decodes incrementally, the
+    /// others report every segment once they are done.
+    pub fn transcribe(
+        &mut self,
+        samples: Vec<f32>,
+        options: &TranscribeOptions,
+        translate: bool,
+        on_segment: Option<&mut dyn FnMut(&Segment)>,
+    ) -> Result<Pass, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Whisper(model) => {
+                let language =
+                    languages::resolve(&options.language).code(ErrorCode::InvalidArgument)?;
+                Ok(model
+                    .transcribe(
+                        &samples,
+                        language.as_deref(),
+                        translate,
+                        options.needs_words(),
+                        on_segment,
+                    )
+                    .code(ErrorCode::InferenceFailed)?)
+            }
+            #[cfg(feature = "parakeet")]
+            Backend::Parakeet(engine) => {
+                let params = ParakeetInferenceParams {
+                    timestamp_granularity: TimestampGranularity::Segment,
+                    ..Default::default()
+                };
+                run(engine, samples, Some(params), on_segment)
+            }
+            #[cfg(feature = "moonshine")]
+            Backend::Moonshine(engine) => run(engine, samples, None, on_segment),
+        }
+    }
+
+    /// Rank every language Whisper knows by how likely the start of
+    /// `samples` is to be spoken in it, most likely first.
+    pub fn detect_language(
+        &mut self,
+        samples: &[f32],
+    ) -> Result<Vec<(&'static str, f32)>, Box<dyn std::error::Error>> {
+        match self {
+            Backend::Whisper(model) => {
+                if !model.is_multilingual() {
+                    return Err(CliError::new(
+                        ErrorCode::InvalidArgument,
+                        "This is an English-only model: language identification needs a multilingual one",
+                    )
+                    .into());
+                }
+                Ok(model
+                    .detect_language(samples)
+                    .code(ErrorCode::InferenceFailed)?)
+            }
+            // Rejected by `check_options`: these engines are English-only.
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("language detection needs the whisper engine"),
+        }
+    }
+
+    pub fn unload(&mut self) {
+        match self {
+            // The whisper.cpp context is freed when the backend is dropped.
+            Backend::Whisper(_) => {}
+            #[cfg(feature = "parakeet")]
+            Backend::Parakeet(engine) => engine.unload_model(),
+            #[cfg(feature = "moonshine")]
+            Backend::Moonshine(engine) => engine.unload_model(),
+        }
+    }
+}
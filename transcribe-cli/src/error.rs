@@ -0,0 +1,110 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// Stable, machine-readable failure categories reported in `ErrorOutput`.
+/// Each maps to its own process exit code so scripts can branch without
+/// parsing messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidArgument,
+    ModelNotFound,
+    ModelLoadFailed,
+    AudioNotFound,
+    UnsupportedAudioFormat,
+    AudioDecodeFailed,
+    InferenceFailed,
+    ModelCorrupt,
+    DownloadFailed,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCode::Internal => 1,
+            // Matches clap's exit code for usage errors.
+            ErrorCode::InvalidArgument => 2,
+            ErrorCode::ModelNotFound => 3,
+            ErrorCode::ModelLoadFailed => 4,
+            ErrorCode::AudioNotFound => 5,
+            ErrorCode::UnsupportedAudioFormat => 6,
+            ErrorCode::AudioDecodeFailed => 7,
+            ErrorCode::InferenceFailed => 8,
+            ErrorCode::ModelCorrupt => 9,
+            ErrorCode::DownloadFailed => 10,
+        }
+    }
+
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            ErrorCode::InvalidArgument => {
+                Some("Run transcribe-cli --help for the accepted options")
+            }
+            ErrorCode::ModelNotFound => {
+                Some("Re-run the Vocord installer to download the model, or check the --model path")
+            }
+            ErrorCode::ModelLoadFailed => Some(
+                "The model file may be corrupt or incompatible: re-download it. \
+                 If the GPU driver is the problem, retry with --device cpu",
+            ),
+            ErrorCode::AudioNotFound => {
+                Some("Check the --audio path and that the file still exists")
+            }
+            ErrorCode::UnsupportedAudioFormat => {
+                Some("Use Ogg/Opus, or WAV at 8-96 kHz with 8/16/24/32-bit integer or 32-bit float samples")
+            }
+            ErrorCode::AudioDecodeFailed => {
+                Some("The audio file is truncated or corrupt: download it again")
+            }
+            ErrorCode::InferenceFailed => {
+                Some("Retry; if it keeps failing, try --device cpu or a different model")
+            }
+            ErrorCode::ModelCorrupt => Some(
+                "The model file is damaged: download it again with `transcribe-cli models download`",
+            ),
+            ErrorCode::DownloadFailed => {
+                Some("Check your connection and retry; interrupted model downloads resume where they stopped")
+            }
+            ErrorCode::Internal => None,
+        }
+    }
+}
+
+/// An error tagged with its category. Functions keep returning
+/// `Box<dyn Error>`; `ErrorOutput` downcasts to this type to recover the code,
+/// and anything untagged is reported as `internal`.
+#[derive(Debug)]
+pub struct CliError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Tag any displayable error with an `ErrorCode`.
+pub trait ResultExt<T> {
+    fn code(self, code: ErrorCode) -> Result<T, CliError>;
+}
+
+impl<T, E: fmt::Display> ResultExt<T> for Result<T, E> {
+    fn code(self, code: ErrorCode) -> Result<T, CliError> {
+        self.map_err(|e| CliError::new(code, e.to_string()))
+    }
+}
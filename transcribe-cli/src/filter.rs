@@ -0,0 +1,330 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::transcribe::{Segment, Word};
+
+/// A word sequence repeated back to back this many times is a decoding loop,
+/// not speech ("okay okay okay okay").
+const MIN_REPEATS: usize = 4;
+
+/// Longest repeated phrase, in words, that loop detection looks for.
+const MAX_LOOP_WORDS: usize = 8;
+
+/// Stock phrases Whisper emits on silence and noise, learned from subtitle
+/// credits in its training data. Compared after `normalize`, and only
+/// against whole segments, so speech that merely contains them survives.
+const KNOWN_HALLUCINATIONS: &[&str] = &[
+    "thank you for watching",
+    "thanks for watching",
+    "thank you for watching please subscribe",
+    "please subscribe to my channel",
+    "like and subscribe",
+    "subtitles by the amaraorg community",
+    "subtitles by steamteam",
+    "transcription by castingwords",
+    "sous titres réalisés par la communauté damaraorg",
+    "merci davoir regardé cette vidéo",
+    "untertitel der amaraorg community",
+    "untertitel im auftrag des zdf",
+    "subtítulos realizados por la comunidad de amaraorg",
+    "gracias por ver el video",
+    "ご視聴ありがとうございました",
+    "字幕由amaraorg社区提供",
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// A phrase repeated in a loop; collapsed to one occurrence.
+    Repetition,
+    /// A segment that is entirely a known hallucinated phrase.
+    KnownHallucination,
+    /// A segment over a stretch the voice activity detector found silent.
+    /// whisper-rs 0.13 doesn't expose Whisper's own no-speech probability,
+    /// so the energy VAD stands in for it.
+    NoSpeech,
+}
+
+/// Something suspicious found in the transcript. `text` is what the engine
+/// produced; `removed` says whether it was taken out of the output.
+#[derive(Serialize, Deserialize)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<f32>,
+    pub removed: bool,
+}
+
+impl Warning {
+    fn new(kind: WarningKind, text: &str, segment: Option<&Segment>, removed: bool) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+            start: segment.map(|s| s.start),
+            end: segment.map(|s| s.end),
+            removed,
+        }
+    }
+}
+
+/// Lowercase, drop punctuation and collapse whitespace, so "Thank you for
+/// watching!" and "thank you for watching." compare equal.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_known_hallucination(text: &str) -> bool {
+    let normalized = normalize(text);
+    !normalized.is_empty() && KNOWN_HALLUCINATIONS.contains(&normalized.as_str())
+}
+
+/// A phrase of `len` words starting at word `start`, repeated back to back
+/// `repeats` times.
+struct Loop {
+    start: usize,
+    len: usize,
+    repeats: usize,
+}
+
+/// Find every run of a 1-`MAX_LOOP_WORDS` word phrase repeated at least
+/// `MIN_REPEATS` times, in normalized words.
+fn find_loops(keys: &[String]) -> Vec<Loop> {
+    let mut loops = Vec::new();
+    let mut i = 0;
+    'outer: while i < keys.len() {
+        for len in 1..=MAX_LOOP_WORDS.min(keys.len() - i) {
+            let phrase = &keys[i..i + len];
+            if phrase.iter().all(String::is_empty) {
+                continue;
+            }
+            let mut repeats = 1;
+            while keys.get(i + repeats * len..i + (repeats + 1) * len) == Some(phrase) {
+                repeats += 1;
+            }
+            if repeats >= MIN_REPEATS {
+                loops.push(Loop {
+                    start: i,
+                    len,
+                    repeats,
+                });
+                i += repeats * len;
+                continue 'outer;
+            }
+        }
+        i += 1;
+    }
+    loops
+}
+
+/// `word` ending in the trailing punctuation of `last` instead of its own.
+fn with_punctuation_of(word: &str, last: &str) -> String {
+    let is_punctuation = |c: char| !c.is_alphanumeric();
+    let stem = word.trim_end_matches(is_punctuation);
+    let punctuation = &last[last.trim_end_matches(is_punctuation).len()..];
+    format!("{}{}", stem, punctuation)
+}
+
+/// Collapse every loop in `items` to its first occurrence. The kept phrase
+/// ends with the punctuation the loop ended with, so "Okay okay okay okay,
+/// see you" becomes "Okay, see you". Returns whether anything changed.
+fn collapse_runs<T>(items: &mut Vec<T>, text: impl Fn(&mut T) -> &mut String) -> bool {
+    let keys: Vec<String> = items.iter_mut().map(|item| normalize(text(item))).collect();
+    let loops = find_loops(&keys);
+    if loops.is_empty() {
+        return false;
+    }
+
+    for l in &loops {
+        let last = text(&mut items[l.start + l.len * l.repeats - 1]).clone();
+        let kept = text(&mut items[l.start + l.len - 1]);
+        *kept = with_punctuation_of(kept, &last);
+    }
+    let mut index = 0;
+    items.retain(|_| {
+        let repeated = loops
+            .iter()
+            .any(|l| (l.start + l.len..l.start + l.len * l.repeats).contains(&index));
+        index += 1;
+        !repeated
+    });
+    true
+}
+
+/// The text with its loops collapsed, or `None` when it has none.
+fn collapse_loops(text: &str) -> Option<String> {
+    let mut words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+    collapse_runs(&mut words, |w| w).then(|| words.join(" "))
+}
+
+fn overlaps_speech(segment: &Segment, speech: &[Range<f32>]) -> bool {
+    speech
+        .iter()
+        .any(|r| segment.start < r.end && r.start < segment.end)
+}
+
+/// Check one segment, returning whether it stays in the output and the
+/// warning it raised. With `remove`, a hallucinated segment is dropped and a
+/// loop collapsed in place. The `--progress` segment events go through this
+/// too, so they agree with the final output.
+pub fn check_segment(
+    segment: &mut Segment,
+    speech: Option<&[Range<f32>]>,
+    remove: bool,
+) -> (bool, Option<Warning>) {
+    let kind = if is_known_hallucination(&segment.text) {
+        Some(WarningKind::KnownHallucination)
+    } else if speech.is_some_and(|speech| !overlaps_speech(segment, speech)) {
+        Some(WarningKind::NoSpeech)
+    } else {
+        None
+    };
+    if let Some(kind) = kind {
+        let warning = Warning::new(kind, &segment.text, Some(segment), remove);
+        return (!remove, Some(warning));
+    }
+
+    let Some(collapsed) = collapse_loops(&segment.text) else {
+        return (true, None);
+    };
+    let warning = Warning::new(
+        WarningKind::Repetition,
+        &segment.text,
+        Some(segment),
+        remove,
+    );
+    if remove {
+        segment.text = collapsed;
+    }
+    (true, Some(warning))
+}
+
+/// Find decoding loops, stock hallucinations and segments over silence in a
+/// transcript. With `remove`, they are taken out of `text` and `segments`;
+/// otherwise (the default, since real speech can look like a loop: "no no no
+/// no") the output is left alone and only the warnings report them.
+/// `speech` holds the speech regions in seconds, when known.
+pub fn apply(
+    text: &mut String,
+    segments: &mut Vec<Segment>,
+    speech: Option<&[Range<f32>]>,
+    remove: bool,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    // Engines without timestamps give no segments to judge one by one.
+    if segments.is_empty() {
+        if is_known_hallucination(text) {
+            warnings.push(Warning::new(
+                WarningKind::KnownHallucination,
+                text,
+                None,
+                remove,
+            ));
+            if remove {
+                text.clear();
+            }
+        } else {
+            warnings.extend(collapse_text(text, remove));
+        }
+        return warnings;
+    }
+
+    let mut changed = false;
+    segments.retain_mut(|segment| {
+        let (keep, warning) = check_segment(segment, speech, remove);
+        changed |= remove && warning.is_some();
+        warnings.extend(warning);
+        keep
+    });
+
+    if changed {
+        *text = segments
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+    // A loop can also run across segment boundaries. When loops were kept
+    // in place, the whole text would just report them a second time.
+    if remove
+        || !warnings
+            .iter()
+            .any(|w| matches!(w.kind, WarningKind::Repetition))
+    {
+        warnings.extend(collapse_text(text, remove));
+    }
+    warnings
+}
+
+/// Loop detection on a whole text, e.g. a translation or a transcript
+/// without segments.
+pub fn collapse_text(text: &mut String, remove: bool) -> Option<Warning> {
+    let collapsed = collapse_loops(text)?;
+    let warning = Warning::new(WarningKind::Repetition, text, None, remove);
+    if remove {
+        *text = collapsed;
+    }
+    Some(warning)
+}
+
+/// Loop detection on `--words` output, so the words match a text whose
+/// loops were collapsed.
+pub fn collapse_words(words: &mut Vec<Word>) {
+    collapse_runs(words, |w| &mut w.word);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(text: &str) -> Vec<String> {
+        text.split_whitespace().map(normalize).collect()
+    }
+
+    #[test]
+    fn finds_single_word_loop() {
+        let loops = find_loops(&keys("okay okay okay okay okay see you"));
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].start, loops[0].len, loops[0].repeats), (0, 1, 5));
+    }
+
+    #[test]
+    fn finds_phrase_loop_after_other_words() {
+        let loops = find_loops(&keys("so I said see you see you see you see you"));
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].start, loops[0].len, loops[0].repeats), (3, 2, 4));
+    }
+
+    #[test]
+    fn ignores_short_repetition() {
+        assert!(find_loops(&keys("no no no, I said")).is_empty());
+        assert_eq!(collapse_loops("very very very good"), None);
+    }
+
+    #[test]
+    fn collapse_keeps_first_occurrence_and_final_punctuation() {
+        assert_eq!(
+            collapse_loops("Okay okay okay okay okay, see you tomorrow.").as_deref(),
+            Some("Okay, see you tomorrow.")
+        );
+        assert_eq!(
+            collapse_loops("Thanks. See you. See you. See you. See you.").as_deref(),
+            Some("Thanks. See you.")
+        );
+    }
+
+    #[test]
+    fn punctuation_only_words_are_not_a_loop() {
+        assert_eq!(collapse_loops("- - - - -"), None);
+    }
+}
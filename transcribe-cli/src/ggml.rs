@@ -0,0 +1,279 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use crate::error::{CliError, ErrorCode};
+
+/// "ggml" in little-endian, the first four bytes of every whisper.cpp model.
+const MAGIC: u32 = 0x6767_6d6c;
+
+const TRUNCATED: &str = "file is truncated";
+
+/// The hyperparameter block that follows the magic, in file order.
+struct Hparams {
+    n_vocab: i32,
+    n_audio_ctx: i32,
+    n_audio_state: i32,
+    n_audio_head: i32,
+    n_audio_layer: i32,
+    n_text_ctx: i32,
+    n_text_state: i32,
+    n_text_head: i32,
+    n_text_layer: i32,
+    n_mels: i32,
+    ftype: i32,
+}
+
+impl Hparams {
+    /// Every released Whisper model falls well inside these bounds; values
+    /// outside them mean the header is garbage, not an exotic model.
+    fn is_plausible(&self) -> bool {
+        let dims = [
+            self.n_audio_ctx,
+            self.n_audio_state,
+            self.n_audio_head,
+            self.n_audio_layer,
+            self.n_text_ctx,
+            self.n_text_state,
+            self.n_text_head,
+            self.n_text_layer,
+        ];
+        (50_000..=60_000).contains(&self.n_vocab)
+            && dims.iter().all(|d| (1..=8192).contains(d))
+            && matches!(self.n_mels, 80 | 128)
+            && self.ftype >= 0
+    }
+}
+
+/// Bytes per block and elements per block of a ggml tensor type, or `None`
+/// for types this check doesn't know (their size can't be verified).
+fn type_size(ttype: i32) -> Option<(u64, u64)> {
+    Some(match ttype {
+        0 => (4, 1),      // F32
+        1 => (2, 1),      // F16
+        2 => (18, 32),    // Q4_0
+        3 => (20, 32),    // Q4_1
+        6 => (22, 32),    // Q5_0
+        7 => (24, 32),    // Q5_1
+        8 => (34, 32),    // Q8_0
+        10 => (84, 256),  // Q2_K
+        11 => (110, 256), // Q3_K
+        12 => (144, 256), // Q4_K
+        13 => (176, 256), // Q5_K
+        14 => (210, 256), // Q6_K
+        _ => return None,
+    })
+}
+
+fn corrupt(path: &Path, reason: &str) -> CliError {
+    CliError::new(
+        ErrorCode::ModelCorrupt,
+        format!("Model file is corrupt: {} ({})", path.display(), reason),
+    )
+}
+
+/// Sequential reader over the model that tracks its own position, so
+/// truncation is detected without seeking past the end.
+struct Walker<R> {
+    reader: BufReader<R>,
+    pos: u64,
+    len: u64,
+}
+
+impl<R: Read + Seek> Walker<R> {
+    fn i32(&mut self) -> Result<i32, String> {
+        let mut buf = [0u8; 4];
+        self.reader
+            .read_exact(&mut buf)
+            .map_err(|_| TRUNCATED.to_string())?;
+        self.pos += 4;
+        Ok(i32::from_le_bytes(buf))
+    }
+
+    /// Read a non-negative count or length, capped to reject absurd values
+    /// before they drive a huge skip.
+    fn count(&mut self, max: i32) -> Result<u64, String> {
+        let value = self.i32()?;
+        if !(0..=max).contains(&value) {
+            return Err("invalid header".to_string());
+        }
+        Ok(value as u64)
+    }
+
+    fn skip(&mut self, bytes: u64) -> Result<(), String> {
+        if self.pos.saturating_add(bytes) > self.len {
+            return Err(TRUNCATED.to_string());
+        }
+        // Within the buffer this is free; the vocabulary is tens of thousands
+        // of tiny skips.
+        self.reader
+            .seek_relative(bytes as i64)
+            .map_err(|e| e.to_string())?;
+        self.pos += bytes;
+        Ok(())
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.len
+    }
+}
+
+/// Check that a file is a whole whisper.cpp GGML model: the magic, sane
+/// hyperparameters, and every tensor's data present. whisper.cpp itself only
+/// reports truncated or mangled files with opaque errors, or crashes.
+pub fn validate(path: &Path) -> Result<(), CliError> {
+    let file = File::open(path).map_err(|e| {
+        CliError::new(
+            ErrorCode::ModelNotFound,
+            format!("Failed to open model {}: {}", path.display(), e),
+        )
+    })?;
+    let len = file
+        .metadata()
+        .map_err(|e| corrupt(path, &e.to_string()))?
+        .len();
+    let mut walker = Walker {
+        reader: BufReader::new(file),
+        pos: 0,
+        len,
+    };
+
+    walk(&mut walker).map_err(|reason| corrupt(path, &reason))
+}
+
+fn walk(w: &mut Walker<impl Read + Seek>) -> Result<(), String> {
+    if w.i32()? as u32 != MAGIC {
+        return Err("not a GGML model".to_string());
+    }
+
+    let hparams = Hparams {
+        n_vocab: w.i32()?,
+        n_audio_ctx: w.i32()?,
+        n_audio_state: w.i32()?,
+        n_audio_head: w.i32()?,
+        n_audio_layer: w.i32()?,
+        n_text_ctx: w.i32()?,
+        n_text_state: w.i32()?,
+        n_text_head: w.i32()?,
+        n_text_layer: w.i32()?,
+        n_mels: w.i32()?,
+        ftype: w.i32()?,
+    };
+    if !hparams.is_plausible() {
+        return Err("invalid hyperparameters".to_string());
+    }
+
+    // Mel filterbank: n_mel x n_fft f32 values.
+    let n_mel = w.count(1024)?;
+    let n_fft = w.count(1024)?;
+    w.skip(n_mel * n_fft * 4)?;
+
+    // Vocabulary: length-prefixed byte strings.
+    let n_words = w.count(100_000)?;
+    for _ in 0..n_words {
+        let len = w.count(1024)?;
+        w.skip(len)?;
+    }
+
+    // Tensors run to the end of the file, each a small header followed by
+    // its data.
+    let mut tensors = 0;
+    while !w.at_end() {
+        let n_dims = w.count(4)?;
+        let name_len = w.count(256)?;
+        let ttype = w.i32()?;
+
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            elements = elements.saturating_mul(w.count(i32::MAX)?);
+        }
+        w.skip(name_len)?;
+
+        match type_size(ttype) {
+            Some((bytes, block)) => w.skip(elements.div_ceil(block).saturating_mul(bytes))?,
+            // Without the size there's no way to find the next tensor; the
+            // header and everything before it checked out.
+            None => return Ok(()),
+        }
+        tensors += 1;
+    }
+
+    if tensors == 0 {
+        return Err(TRUNCATED.to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn ints(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// A tiny model: real-looking hyperparameters, a one-word vocabulary and
+    /// a single F32 tensor of three elements.
+    fn model() -> Vec<u8> {
+        [
+            ints(&[MAGIC as i32]),
+            ints(&[51864, 1500, 384, 6, 4, 448, 384, 6, 4, 80, 1]),
+            // Mel filterbank, 80 x 201 values.
+            ints(&[80, 201]),
+            vec![0; 80 * 201 * 4],
+            // Vocabulary: one word.
+            ints(&[1, 2]),
+            b"hi".to_vec(),
+            // Tensor header: one dimension, a four byte name, F32, 3 elements.
+            ints(&[1, 4, 0, 3]),
+            b"test".to_vec(),
+            vec![0; 3 * 4],
+        ]
+        .concat()
+    }
+
+    fn check(bytes: Vec<u8>) -> Result<(), String> {
+        let len = bytes.len() as u64;
+        walk(&mut Walker {
+            reader: BufReader::new(Cursor::new(bytes)),
+            pos: 0,
+            len,
+        })
+    }
+
+    #[test]
+    fn accepts_whole_model() {
+        assert_eq!(check(model()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_truncated_tensor() {
+        let mut bytes = model();
+        bytes.pop();
+        assert_eq!(check(bytes), Err(TRUNCATED.to_string()));
+    }
+
+    #[test]
+    fn rejects_wrong_magic() {
+        let mut bytes = model();
+        bytes[0] ^= 0xff;
+        assert_eq!(check(bytes), Err("not a GGML model".to_string()));
+    }
+
+    #[test]
+    fn rejects_implausible_hyperparameters() {
+        let mut bytes = model();
+        // n_vocab
+        bytes[4..8].copy_from_slice(&7i32.to_le_bytes());
+        assert_eq!(check(bytes), Err("invalid hyperparameters".to_string()));
+    }
+
+    #[test]
+    fn rejects_model_without_tensors() {
+        let mut bytes = model();
+        bytes.truncate(bytes.len() - (4 * 4 + 4 + 3 * 4));
+        assert_eq!(check(bytes), Err(TRUNCATED.to_string()));
+    }
+}
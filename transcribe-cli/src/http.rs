@@ -0,0 +1,407 @@
+use std::io::{Cursor, Read};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::audio::SourceFormat;
+use crate::cache::CacheArgs;
+use crate::error::{CliError, ErrorCode};
+use crate::output::{self, OutputFormat};
+use crate::transcribe::{ErrorOutput, ModelArgs, Task, TranscribeOptions, Transcriber};
+
+#[derive(clap::Args)]
+pub struct HttpArgs {
+    /// Address to listen on. Anyone who can reach it can use the model, so
+    /// keep it on localhost unless you mean to share it
+    #[arg(long, default_value = "127.0.0.1:8765")]
+    listen: SocketAddr,
+
+    #[command(flatten)]
+    model: ModelArgs,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+/// Same upload limit as the OpenAI API.
+const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ResponseFormat {
+    Json,
+    Text,
+    Srt,
+    Vtt,
+    VerboseJson,
+}
+
+impl ResponseFormat {
+    fn parse(value: &str) -> Result<Self, CliError> {
+        match value {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            "srt" => Ok(Self::Srt),
+            "vtt" => Ok(Self::Vtt),
+            "verbose_json" => Ok(Self::VerboseJson),
+            _ => Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                format!(
+                    "Unsupported response_format: {} (expected json, text, srt, vtt or verbose_json)",
+                    value
+                ),
+            )),
+        }
+    }
+
+    fn needs_segments(self) -> bool {
+        matches!(self, Self::Srt | Self::Vtt | Self::VerboseJson)
+    }
+}
+
+/// OpenAI-style error body: `{"error": {"message", "type", "code"}}`.
+#[derive(Serialize)]
+struct ApiError {
+    error: ApiErrorBody,
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody {
+    message: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    code: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct VerboseOutput<'a> {
+    task: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+    duration: f32,
+    text: &'a str,
+    segments: Vec<VerboseSegment<'a>>,
+    /// The upload's original format (not part of the OpenAI response).
+    audio: &'a SourceFormat,
+}
+
+#[derive(Serialize)]
+struct VerboseSegment<'a> {
+    id: usize,
+    start: f32,
+    end: f32,
+    text: &'a str,
+}
+
+/// The parts of a multipart/form-data body we care about.
+#[derive(Default)]
+struct Form {
+    file: Option<Vec<u8>>,
+    language: Option<String>,
+    response_format: Option<String>,
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+fn bad_request(message: &str) -> CliError {
+    CliError::new(ErrorCode::InvalidArgument, message)
+}
+
+/// Extract a `key="value"` parameter from a Content-Disposition header.
+fn disposition_param(headers: &str, key: &str) -> Option<String> {
+    let line = headers
+        .lines()
+        .find(|l| l.to_ascii_lowercase().starts_with("content-disposition:"))?;
+    line.split(';').skip(1).find_map(|param| {
+        let (k, v) = param.trim().split_once('=')?;
+        (k.eq_ignore_ascii_case(key)).then(|| v.trim_matches('"').to_string())
+    })
+}
+
+/// Minimal multipart/form-data parser, enough for the OpenAI audio API's
+/// `file` upload and its text fields.
+fn parse_multipart(content_type: &str, body: &[u8]) -> Result<Form, CliError> {
+    let boundary = content_type
+        .split(';')
+        .find_map(|p| p.trim().strip_prefix("boundary="))
+        .map(|b| b.trim_matches('"'))
+        .filter(|b| content_type.starts_with("multipart/form-data") && !b.is_empty())
+        .ok_or_else(|| bad_request("Expected a multipart/form-data request body"))?;
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let malformed = || bad_request("Malformed multipart/form-data body");
+
+    let mut form = Form::default();
+    let mut pos = find(body, &delimiter, 0).ok_or_else(malformed)? + delimiter.len();
+
+    // Each part is `\r\n<headers>\r\n\r\n<content>\r\n--boundary`; the final
+    // delimiter is followed by `--` instead of `\r\n`.
+    while !body[pos..].starts_with(b"--") {
+        let start = pos + 2;
+        let next = find(body, &delimiter, start).ok_or_else(malformed)?;
+        let part = body
+            .get(start..next.saturating_sub(2))
+            .ok_or_else(malformed)?;
+
+        let header_end = find(part, b"\r\n\r\n", 0).ok_or_else(malformed)?;
+        let headers = String::from_utf8_lossy(&part[..header_end]);
+        let content = &part[header_end + 4..];
+        let text = || String::from_utf8_lossy(content).trim().to_string();
+
+        match disposition_param(&headers, "name").as_deref() {
+            Some("file") => form.file = Some(content.to_vec()),
+            Some("language") => form.language = Some(text()),
+            Some("response_format") => form.response_format = Some(text()),
+            // `model`, `prompt`, `temperature`, ... are accepted and ignored:
+            // the server runs the one model it was started with.
+            _ => {}
+        }
+
+        pos = next + delimiter.len();
+    }
+
+    Ok(form)
+}
+
+fn header_value(request: &Request, name: &str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str().to_string())
+}
+
+fn respond(status: u16, content_type: &str, body: Vec<u8>) -> HttpResponse {
+    let header = Header::from_bytes("Content-Type", content_type).expect("valid header");
+    Response::from_data(body)
+        .with_status_code(StatusCode(status))
+        .with_header(header)
+}
+
+fn json_response<T: Serialize>(status: u16, value: &T) -> HttpResponse {
+    let body = serde_json::to_vec(value).expect("failed to serialize response");
+    respond(status, "application/json", body)
+}
+
+fn error_response(error: &(dyn std::error::Error + 'static)) -> HttpResponse {
+    let output = ErrorOutput::new(error);
+    let (status, kind) = match output.code {
+        ErrorCode::InvalidArgument
+        | ErrorCode::AudioNotFound
+        | ErrorCode::UnsupportedAudioFormat
+        | ErrorCode::AudioDecodeFailed => (400, "invalid_request_error"),
+        _ => (500, "server_error"),
+    };
+    json_response(
+        status,
+        &ApiError {
+            error: ApiErrorBody {
+                message: output.error,
+                kind,
+                code: output.code,
+                hint: output.hint,
+            },
+        },
+    )
+}
+
+fn transcription(
+    transcriber: &mut Transcriber,
+    request: &mut Request,
+    task: Task,
+) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let content_type = header_value(request, "Content-Type").unwrap_or_default();
+
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_UPLOAD_BYTES as u64 + 1)
+        .read_to_end(&mut body)?;
+    if body.len() > MAX_UPLOAD_BYTES {
+        return Err(bad_request("Upload exceeds the 25 MB limit").into());
+    }
+
+    let form = parse_multipart(&content_type, &body)?;
+    let file = form
+        .file
+        .ok_or_else(|| bad_request("Missing 'file' field in the form data"))?;
+    let format = ResponseFormat::parse(form.response_format.as_deref().unwrap_or("json"))?;
+
+    let options = TranscribeOptions {
+        language: form.language.clone().unwrap_or_else(|| "auto".to_string()),
+        segments: format.needs_segments(),
+        task,
+        ..Default::default()
+    };
+    let decoded = crate::audio::decode_bytes(&file, "uploaded file")?;
+    let duration = decoded.samples.len() as f32 / crate::audio::SAMPLE_RATE as f32;
+
+    let output = transcriber.transcribe(decoded.samples, &options)?;
+
+    let response = match format {
+        ResponseFormat::Json => json_response(200, &serde_json::json!({ "text": output.text })),
+        ResponseFormat::Text => respond(
+            200,
+            "text/plain; charset=utf-8",
+            output::render(&output, OutputFormat::Text).into_bytes(),
+        ),
+        ResponseFormat::Srt => respond(
+            200,
+            "text/plain; charset=utf-8",
+            output::render(&output, OutputFormat::Srt).into_bytes(),
+        ),
+        ResponseFormat::Vtt => respond(
+            200,
+            "text/vtt; charset=utf-8",
+            output::render(&output, OutputFormat::Vtt).into_bytes(),
+        ),
+        ResponseFormat::VerboseJson => {
+            let segments = output.segments.as_deref().unwrap_or(&[]);
+            json_response(
+                200,
+                &VerboseOutput {
+                    task: if task == Task::Translate {
+                        "translate"
+                    } else {
+                        "transcribe"
+                    },
+                    language: output.language.as_deref().or(form.language.as_deref()),
+                    duration,
+                    text: &output.text,
+                    audio: &decoded.format,
+                    segments: segments
+                        .iter()
+                        .enumerate()
+                        .map(|(id, s)| VerboseSegment {
+                            id,
+                            start: s.start,
+                            end: s.end,
+                            text: &s.text,
+                        })
+                        .collect(),
+                },
+            )
+        }
+    };
+    Ok(response)
+}
+
+fn handle(transcriber: &mut Transcriber, request: &mut Request) -> HttpResponse {
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+
+    let task = match path.as_str() {
+        "/v1/audio/transcriptions" => Task::Transcribe,
+        "/v1/audio/translations" => Task::Translate,
+        _ => {
+            return json_response(
+                404,
+                &serde_json::json!({ "error": { "message": "Not found" } }),
+            )
+        }
+    };
+    if *request.method() != Method::Post {
+        return json_response(
+            405,
+            &serde_json::json!({ "error": { "message": "Method not allowed" } }),
+        );
+    }
+
+    match transcription(transcriber, request, task) {
+        Ok(response) => response,
+        Err(e) => error_response(e.as_ref()),
+    }
+}
+
+pub fn run(args: HttpArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut transcriber = Transcriber::new(args.model, args.cache.open());
+    transcriber.engine()?;
+
+    let server = Server::http(args.listen)
+        .map_err(|e| format!("Failed to listen on {}: {}", args.listen, e))?;
+    let server = Arc::new(server);
+    let handler_server = Arc::clone(&server);
+    ctrlc::set_handler(move || handler_server.unblock())?;
+    eprintln!("[http] Listening on http://{}", args.listen);
+
+    // Requests are handled one at a time: the engine is not shareable, and
+    // whisper.cpp already uses every core for a single transcription.
+    for mut request in server.incoming_requests() {
+        let response = handle(&mut transcriber, &mut request);
+        if let Err(e) = request.respond(response) {
+            eprintln!("[http] Failed to send response: {}", e);
+        }
+    }
+
+    eprintln!("[http] Shutting down");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT_TYPE: &str = "multipart/form-data; boundary=XyZ";
+
+    fn body(parts: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (disposition, content) in parts {
+            body.extend_from_slice(b"--XyZ\r\nContent-Disposition: form-data; ");
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(b"\r\n\r\n");
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--XyZ--\r\n");
+        body
+    }
+
+    #[test]
+    fn parses_file_and_fields() {
+        let audio: &[u8] = b"OggS\r\n--Xy\x00\xff";
+        let body = body(&[
+            ("name=\"model\"", b"whisper-1"),
+            ("name=\"file\"; filename=\"voice.ogg\"", audio),
+            ("name=\"language\"", b" de "),
+            ("name=\"response_format\"", b"srt"),
+        ]);
+        let form = parse_multipart(CONTENT_TYPE, &body).unwrap();
+        assert_eq!(form.file.as_deref(), Some(audio));
+        assert_eq!(form.language.as_deref(), Some("de"));
+        assert_eq!(form.response_format.as_deref(), Some("srt"));
+    }
+
+    #[test]
+    fn accepts_quoted_boundary() {
+        let body = body(&[("name=\"file\"", b"data")]);
+        let form = parse_multipart("multipart/form-data; boundary=\"XyZ\"", &body).unwrap();
+        assert_eq!(form.file.as_deref(), Some(&b"data"[..]));
+    }
+
+    #[test]
+    fn rejects_other_content_types() {
+        let body = body(&[("name=\"file\"", b"data")]);
+        assert!(parse_multipart("application/json", &body).is_err());
+        assert!(parse_multipart("multipart/form-data", &body).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        let whole = body(&[("name=\"file\"", b"data")]);
+        // Missing closing delimiter.
+        assert!(parse_multipart(CONTENT_TYPE, &whole[..whole.len() - 9]).is_err());
+        // Part without a blank line after its headers.
+        assert!(parse_multipart(CONTENT_TYPE, b"--XyZ\r\nname=\"file\"\r\n--XyZ--").is_err());
+        assert!(parse_multipart(CONTENT_TYPE, b"").is_err());
+        assert!(parse_multipart(CONTENT_TYPE, b"--XyZ").is_err());
+    }
+}
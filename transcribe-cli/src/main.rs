@@ -1,82 +1,213 @@
+mod audio;
+mod batch;
+mod cache;
+mod detect;
+mod engine;
+mod error;
+mod fetch;
+mod filter;
+mod ggml;
+mod http;
+mod languages;
+mod models;
+mod output;
+mod progress;
+mod serve;
+mod stream;
+mod transcribe;
+mod vad;
+mod whisper;
+
 use std::io::Write;
 use std::path::PathBuf;
 use std::process;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use serde::Serialize;
-use transcribe_rs::{
-    engines::whisper::{WhisperEngine, WhisperInferenceParams, WhisperModelParams},
-    TranscriptionEngine,
-};
+
+use cache::{Cache, CacheArgs};
+use error::{CliError, ErrorCode};
+use output::OutputFormat;
+use transcribe::{ErrorOutput, ModelArgs, TranscribeOptions, Transcriber};
 
 #[derive(Parser)]
-#[command(name = "transcribe-cli", about = "Transcribe audio files using Whisper")]
+#[command(
+    name = "transcribe-cli",
+    about = "Transcribe audio files using Whisper",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// Path to the WAV audio file (16kHz, 16-bit, mono)
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the audio file (Ogg/Opus, or WAV at 8-96 kHz in any bit depth
+    /// and channel count), or
+    /// "-" to read it from stdin. Repeat it, or pass a directory or glob
+    /// pattern, to transcribe a batch
+    #[arg(long, required_unless_present = "url", num_args = 1..)]
+    audio: Vec<PathBuf>,
+
+    /// Download the audio from a Discord CDN URL (HTTPS only) instead of
+    /// reading a file
+    #[arg(long, conflicts_with = "audio")]
+    url: Option<String>,
+
+    /// Output format written to stdout
+    #[arg(long, value_enum, default_value = "json")]
+    format: OutputFormat,
+
+    /// Also report the input's original audio format in the JSON output
     #[arg(long)]
-    audio: PathBuf,
+    verbose: bool,
 
-    /// Path to the Whisper GGML model file
+    /// Write NDJSON progress and segment events to stderr while transcribing
     #[arg(long)]
-    model: PathBuf,
+    progress: bool,
+
+    #[command(flatten)]
+    model: ModelArgs,
+
+    #[command(flatten)]
+    options: TranscribeOptions,
+
+    #[command(flatten)]
+    cache: CacheArgs,
 }
 
-#[derive(Serialize)]
-struct SuccessOutput {
-    text: String,
+#[derive(Subcommand)]
+enum Command {
+    /// Keep the model loaded and serve transcription requests over a Unix socket
+    Serve(serve::ServeArgs),
+    /// Serve an OpenAI-compatible transcription API over HTTP
+    Http(http::HttpArgs),
+    /// Transcribe live 16 kHz mono PCM from stdin, printing NDJSON captions
+    Stream(stream::StreamArgs),
+    /// Identify the spoken language from the first 30 seconds, without transcribing
+    DetectLanguage(detect::DetectArgs),
+    /// Manage Whisper GGML models: list, download, verify, remove
+    Models {
+        #[command(subcommand)]
+        action: models::ModelsCommand,
+    },
+    /// Manage the transcript cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Delete all cached transcripts
+    Clear {
+        /// Cache directory (default: the user cache dir)
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+    },
 }
 
 #[derive(Serialize)]
-struct ErrorOutput {
-    error: String,
+struct ClearOutput {
+    removed: usize,
 }
 
-fn run(args: Args) -> Result<String, Box<dyn std::error::Error>> {
-    // Validate paths upfront to produce actionable error messages before
-    // handing them off to the engine, which may emit opaque C-level errors.
-    if !args.model.exists() {
-        return Err(format!(
-            "Model file not found: {}",
-            args.model.display()
-        )
-        .into());
+fn run_cache(action: CacheAction) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        CacheAction::Clear { cache_dir } => {
+            let cache = Cache::new(cache_dir.unwrap_or_else(cache::default_dir), 0);
+            let removed = cache.clear()?;
+            println!("{}", serde_json::to_string(&ClearOutput { removed })?);
+        }
     }
-    if !args.audio.exists() {
-        return Err(format!(
-            "Audio file not found: {}",
-            args.audio.display()
-        )
-        .into());
+    Ok(())
+}
+
+fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    if args.format.needs_segments() {
+        args.options.segments = true;
     }
 
-    let mut engine = WhisperEngine::new();
-    engine.load_model_with_params(&args.model, WhisperModelParams { use_gpu: true })?;
+    // Validate everything cheap before loading the model, so bad input fails
+    // fast instead of after a multi-second model load.
+    engine::check_options(args.model.engine, &args.options)?;
+
+    if args.url.is_none() && batch::is_batch(&args.audio) {
+        if args.format != OutputFormat::Json {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                "Batch transcription only supports --format json",
+            )
+            .into());
+        }
+        let files = batch::expand_inputs(&args.audio)?;
+        let mut transcriber =
+            Transcriber::new(args.model, args.cache.open()).with_progress(args.progress);
+        return batch::run(&mut transcriber, &files, &args.options, args.verbose);
+    }
+
+    let decoded = match &args.url {
+        Some(url) => audio::decode_bytes(&fetch::fetch(url)?, url)?,
+        None => transcribe::load_audio(&args.audio[0])?,
+    };
+    let mut transcriber =
+        Transcriber::new(args.model, args.cache.open()).with_progress(args.progress);
+    let mut output = transcriber.transcribe(decoded.samples, &args.options)?;
+    if args.verbose {
+        output.audio = Some(decoded.format);
+    }
+    println!("{}", output::render(&output, args.format));
+    Ok(())
+}
 
-    let result = engine.transcribe_file(&args.audio, Some(WhisperInferenceParams::default()))?;
-    Ok(result.text)
+fn fail(e: &(dyn std::error::Error + 'static)) -> ! {
+    let output = ErrorOutput::new(e);
+    let json = serde_json::to_string(&output).expect("failed to serialize error");
+    // Flush stderr explicitly before process::exit so the output is
+    // not lost on platforms that buffer stderr.
+    let _ = writeln!(std::io::stderr(), "{}", json);
+    let _ = std::io::stderr().flush();
+    process::exit(output.code.exit_code());
 }
 
 fn main() {
-    let args = Args::parse();
-
-    match run(args) {
-        Ok(text) => {
-            let output = SuccessOutput { text };
-            println!(
-                "{}",
-                serde_json::to_string(&output).expect("failed to serialize output")
-            );
+    let mut args = Args::parse();
+
+    match args.command.take() {
+        Some(Command::Serve(serve_args)) => {
+            if let Err(e) = serve::run(serve_args) {
+                fail(e.as_ref());
+            }
+        }
+        Some(Command::DetectLanguage(detect_args)) => {
+            if let Err(e) = detect::run(detect_args) {
+                fail(e.as_ref());
+            }
+        }
+        Some(Command::Models { action }) => {
+            if let Err(e) = models::run(action) {
+                fail(e.as_ref());
+            }
+        }
+        Some(Command::Cache { action }) => {
+            if let Err(e) = run_cache(action) {
+                fail(e.as_ref());
+            }
+        }
+        Some(Command::Http(http_args)) => {
+            if let Err(e) = http::run(http_args) {
+                fail(e.as_ref());
+            }
+        }
+        Some(Command::Stream(stream_args)) => {
+            if let Err(e) = stream::run(stream_args) {
+                fail(e.as_ref());
+            }
         }
-        Err(e) => {
-            let output = ErrorOutput {
-                error: e.to_string(),
-            };
-            let json = serde_json::to_string(&output).expect("failed to serialize error");
-            // Flush stderr explicitly before process::exit so the output is
-            // not lost on platforms that buffer stderr.
-            let _ = writeln!(std::io::stderr(), "{}", json);
-            let _ = std::io::stderr().flush();
-            process::exit(1);
+        None => {
+            if let Err(e) = run(args) {
+                fail(e.as_ref());
+            }
         }
     }
 }
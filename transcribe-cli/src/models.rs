@@ -0,0 +1,555 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Subcommand;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::{CliError, ErrorCode, ResultExt};
+
+/// Where whisper.cpp publishes its GGML conversions.
+const DEFAULT_MIRROR: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A stall this long aborts the download; a resumed run picks up the `.part`.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct KnownModel {
+    pub name: &'static str,
+    pub file: &'static str,
+    /// Approximate download size, for display only.
+    pub size_mb: u64,
+    /// Pinned SHA-256, the digest Hugging Face lists for the LFS file. The
+    /// mirror is never asked for one, so an entry without a pin can only be
+    /// downloaded with an explicit `--sha256`.
+    pub sha256: Option<&'static str>,
+}
+
+pub const REGISTRY: &[KnownModel] = &[
+    KnownModel {
+        name: "tiny",
+        file: "ggml-tiny.bin",
+        size_mb: 75,
+        sha256: None,
+    },
+    KnownModel {
+        name: "tiny.en",
+        file: "ggml-tiny.en.bin",
+        size_mb: 75,
+        sha256: None,
+    },
+    KnownModel {
+        name: "base",
+        file: "ggml-base.bin",
+        size_mb: 142,
+        sha256: None,
+    },
+    KnownModel {
+        name: "base.en",
+        file: "ggml-base.en.bin",
+        size_mb: 142,
+        sha256: None,
+    },
+    KnownModel {
+        name: "small",
+        file: "ggml-small.bin",
+        size_mb: 466,
+        sha256: None,
+    },
+    KnownModel {
+        name: "small.en",
+        file: "ggml-small.en.bin",
+        size_mb: 466,
+        sha256: None,
+    },
+    KnownModel {
+        name: "medium",
+        file: "ggml-medium.bin",
+        size_mb: 1500,
+        sha256: None,
+    },
+    KnownModel {
+        name: "large-v3",
+        file: "ggml-large-v3.bin",
+        size_mb: 2900,
+        sha256: None,
+    },
+    KnownModel {
+        name: "large-v3-turbo",
+        file: "ggml-large-v3-turbo.bin",
+        size_mb: 1500,
+        sha256: None,
+    },
+    KnownModel {
+        name: "large-v3-turbo-q5_0",
+        file: "ggml-large-v3-turbo-q5_0.bin",
+        size_mb: 547,
+        sha256: None,
+    },
+    KnownModel {
+        name: "large-v3-turbo-q8_0",
+        file: "ggml-large-v3-turbo-q8_0.bin",
+        size_mb: 834,
+        sha256: None,
+    },
+];
+
+#[derive(Subcommand)]
+pub enum ModelsCommand {
+    /// List known models and whether they are installed
+    List {
+        #[command(flatten)]
+        dir: DirArgs,
+    },
+    /// Download a model, resuming a partial download, and verify its checksum
+    Download {
+        /// Model name (see `models list`)
+        name: String,
+        #[command(flatten)]
+        dir: DirArgs,
+        /// Base URL the model files are fetched from
+        #[arg(long, env = "VOCORD_MODEL_MIRROR", default_value = DEFAULT_MIRROR)]
+        mirror: String,
+        /// SHA-256 the file must match; required for models without a pin
+        /// in the registry
+        #[arg(long)]
+        sha256: Option<String>,
+    },
+    /// Check an installed model's structure and compare its hash with the
+    /// expected checksum
+    Verify {
+        /// Model name (see `models list`)
+        name: String,
+        #[command(flatten)]
+        dir: DirArgs,
+    },
+    /// Delete an installed model
+    Remove {
+        /// Model name (see `models list`)
+        name: String,
+        #[command(flatten)]
+        dir: DirArgs,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct DirArgs {
+    /// Directory models are stored in (default: ~/.local/share/vocord)
+    #[arg(long)]
+    dir: Option<PathBuf>,
+}
+
+impl DirArgs {
+    fn resolve(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(default_dir)
+    }
+}
+
+#[derive(Serialize)]
+struct ListEntry {
+    name: &'static str,
+    file: &'static str,
+    size_mb: u64,
+    installed: bool,
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct ModelOutput {
+    name: String,
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+/// The same data directory the Vocord plugin and installer use.
+pub fn default_dir() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    home.join(".local").join("share").join("vocord")
+}
+
+fn lookup(name: &str) -> Result<&'static KnownModel, CliError> {
+    REGISTRY.iter().find(|m| m.name == name).ok_or_else(|| {
+        CliError::new(
+            ErrorCode::InvalidArgument,
+            format!("Unknown model: {} (run `transcribe-cli models list`)", name),
+        )
+    })
+}
+
+/// Path of the checksum sidecar written next to a verified model, in
+/// `sha256sum` format.
+fn sidecar_path(model: &Path) -> PathBuf {
+    let mut name = model.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// Read the digest from a `sha256sum`-style sidecar (`<hex>  <file>`), or a
+/// file containing just the hex digest.
+fn read_sidecar(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(parse_digest(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse_digest(text: &str) -> Option<String> {
+    let token = text.split_whitespace().next()?.trim_matches('"');
+    (token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| token.to_ascii_lowercase())
+}
+
+/// Stream a file through SHA-256.
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn download_error(message: impl Into<String>) -> CliError {
+    CliError::new(ErrorCode::DownloadFailed, message)
+}
+
+/// Work out which digest the download must match: the registry pin, or the
+/// one given with `--sha256`. Whatever the mirror itself publishes is not
+/// trusted, since a compromised mirror would publish the digest of its own
+/// file.
+fn expected_sha256(model: &KnownModel, given: Option<&str>) -> Result<String, CliError> {
+    let given = given
+        .map(|digest| {
+            parse_digest(digest).ok_or_else(|| {
+                CliError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("--sha256 must be 64 hex digits, got {}", digest),
+                )
+            })
+        })
+        .transpose()?;
+    match (model.sha256, given) {
+        (Some(pinned), Some(given)) if pinned != given => Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "--sha256 {} does not match the pinned SHA-256 of {} ({})",
+                given, model.file, pinned
+            ),
+        )),
+        (Some(pinned), _) => Ok(pinned.to_string()),
+        (None, Some(given)) => Ok(given),
+        (None, None) => Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "No SHA-256 is pinned for {}: pass --sha256 with the digest Hugging Face lists for the file",
+                model.file
+            ),
+        )),
+    }
+}
+
+fn download(
+    model: &KnownModel,
+    dir: &Path,
+    mirror: &str,
+    sha256: Option<&str>,
+) -> Result<ModelOutput, Box<dyn std::error::Error>> {
+    let expected = expected_sha256(model, sha256)?;
+    fs::create_dir_all(dir)?;
+    let path = dir.join(model.file);
+    let part = dir.join(format!("{}.part", model.file));
+    let url = format!("{}/{}", mirror.trim_end_matches('/'), model.file);
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+
+    let offset = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+    let mut request = agent.get(&url);
+    if offset > 0 {
+        eprintln!("Resuming {} from byte {}", model.file, offset);
+        request = request.set("Range", &format!("bytes={}-", offset));
+    }
+
+    let response = match request.call() {
+        Ok(response) => Some(response),
+        // The partial file is already complete.
+        Err(ureq::Error::Status(416, _)) if offset > 0 => None,
+        Err(ureq::Error::Status(code, _)) => {
+            return Err(download_error(format!("Failed to download {}: HTTP {}", url, code)).into())
+        }
+        Err(e) => return Err(download_error(format!("Failed to download {}: {}", url, e)).into()),
+    };
+
+    if let Some(response) = response {
+        // 206 resumes the partial file; a plain 200 means the server ignored
+        // the range and is sending the whole file again.
+        let append = response.status() == 206;
+        let total = response
+            .header("Content-Length")
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(|len| if append { len + offset } else { len });
+        let mut file = File::options()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&part)?;
+        let written = if append { offset } else { 0 };
+        copy_with_progress(response.into_reader(), &mut file, written, total)
+            .code(ErrorCode::DownloadFailed)?;
+        file.sync_all()?;
+    }
+
+    eprintln!("Verifying {}...", model.file);
+    let actual = sha256_file(&part)?;
+    if actual != expected {
+        // A corrupt partial file would poison every later resume.
+        fs::remove_file(&part)?;
+        return Err(CliError::new(
+            ErrorCode::ModelCorrupt,
+            format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                model.file, expected, actual
+            ),
+        )
+        .into());
+    }
+
+    fs::write(sidecar_path(&path), format!("{}  {}\n", actual, model.file))?;
+    // Only a verified file ever appears under the final name.
+    fs::rename(&part, &path)?;
+
+    Ok(ModelOutput {
+        name: model.name.to_string(),
+        path,
+        sha256: Some(actual),
+    })
+}
+
+fn copy_with_progress(
+    mut reader: impl Read,
+    writer: &mut impl Write,
+    mut written: u64,
+    total: Option<u64>,
+) -> io::Result<()> {
+    let mut buf = vec![0u8; 1 << 16];
+    let mut last_percent = None;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        writer.write_all(&buf[..n])?;
+        written += n as u64;
+
+        if let Some(total) = total.filter(|t| *t > 0) {
+            let percent = written * 100 / total;
+            if last_percent != Some(percent) {
+                eprint!("\rDownloading... {}%", percent);
+                last_percent = Some(percent);
+                if percent == 100 {
+                    eprintln!();
+                }
+            }
+        }
+    }
+}
+
+fn installed_path(model: &KnownModel, dir: &Path) -> Result<PathBuf, CliError> {
+    let path = dir.join(model.file);
+    if !path.exists() {
+        return Err(CliError::new(
+            ErrorCode::ModelNotFound,
+            format!("Model {} is not installed in {}", model.name, dir.display()),
+        ));
+    }
+    Ok(path)
+}
+
+/// Hash a model and compare it with its registry pin or sidecar.
+pub fn verify_file(
+    path: &Path,
+    pinned: Option<&str>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let expected = match pinned {
+        Some(pinned) => pinned.to_string(),
+        None => read_sidecar(&sidecar_path(path))?.ok_or_else(|| {
+            CliError::new(
+                ErrorCode::ModelCorrupt,
+                format!(
+                    "No checksum recorded for {}: re-download it with `transcribe-cli models download`",
+                    path.display()
+                ),
+            )
+        })?,
+    };
+
+    let actual = sha256_file(path)?;
+    if actual != expected {
+        return Err(CliError::new(
+            ErrorCode::ModelCorrupt,
+            format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                path.display(),
+                expected,
+                actual
+            ),
+        )
+        .into());
+    }
+    Ok(actual)
+}
+
+pub fn run(command: ModelsCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        ModelsCommand::List { dir } => {
+            let dir = dir.resolve();
+            let entries: Vec<ListEntry> = REGISTRY
+                .iter()
+                .map(|m| {
+                    let path = dir.join(m.file);
+                    ListEntry {
+                        name: m.name,
+                        file: m.file,
+                        size_mb: m.size_mb,
+                        installed: path.exists(),
+                        path,
+                    }
+                })
+                .collect();
+            println!("{}", serde_json::to_string(&entries)?);
+        }
+        ModelsCommand::Download {
+            name,
+            dir,
+            mirror,
+            sha256,
+        } => {
+            let model = lookup(&name)?;
+            let output = download(model, &dir.resolve(), &mirror, sha256.as_deref())?;
+            println!("{}", serde_json::to_string(&output)?);
+        }
+        ModelsCommand::Verify { name, dir } => {
+            let model = lookup(&name)?;
+            let path = installed_path(model, &dir.resolve())?;
+            crate::ggml::validate(&path)?;
+            let sha256 = verify_file(&path, model.sha256)?;
+            let output = ModelOutput {
+                name,
+                path,
+                sha256: Some(sha256),
+            };
+            println!("{}", serde_json::to_string(&output)?);
+        }
+        ModelsCommand::Remove { name, dir } => {
+            let model = lookup(&name)?;
+            let path = installed_path(model, &dir.resolve())?;
+            fs::remove_file(&path)?;
+            let _ = fs::remove_file(sidecar_path(&path));
+            println!(
+                "{}",
+                serde_json::to_string(&ModelOutput {
+                    name,
+                    path,
+                    sha256: None
+                })?
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "8b1a9953c4611296a827abf8c47804d7e6c49c6b2b1b0f4e1d6c1e1f9f8e7a6b";
+
+    fn unpinned() -> KnownModel {
+        KnownModel {
+            name: "test",
+            file: "ggml-test.bin",
+            size_mb: 1,
+            sha256: None,
+        }
+    }
+
+    #[test]
+    fn parses_bare_and_sha256sum_digests() {
+        assert_eq!(parse_digest(DIGEST).as_deref(), Some(DIGEST));
+        assert_eq!(
+            parse_digest(&format!("{}  ggml-test.bin\n", DIGEST.to_uppercase())).as_deref(),
+            Some(DIGEST)
+        );
+        assert_eq!(
+            parse_digest(&format!("\"{}\"", DIGEST)).as_deref(),
+            Some(DIGEST)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_digests() {
+        assert_eq!(parse_digest(""), None);
+        assert_eq!(parse_digest(&DIGEST[1..]), None);
+        assert_eq!(parse_digest(&DIGEST.replace('8', "g")), None);
+    }
+
+    #[test]
+    fn verifies_against_sidecar() {
+        let dir =
+            std::env::temp_dir().join(format!("transcribe-cli-sidecar-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let model = dir.join("ggml-test.bin");
+        fs::write(&model, b"hello").unwrap();
+        let code = |err: Box<dyn std::error::Error>| err.downcast_ref::<CliError>().map(|e| e.code);
+
+        let err = verify_file(&model, None).unwrap_err();
+        assert_eq!(code(err), Some(ErrorCode::ModelCorrupt));
+
+        let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        fs::write(sidecar_path(&model), format!("{}  ggml-test.bin\n", hello)).unwrap();
+        assert_eq!(verify_file(&model, None).unwrap(), hello);
+
+        fs::write(&model, b"hellp").unwrap();
+        let err = verify_file(&model, None).unwrap_err();
+        assert_eq!(code(err), Some(ErrorCode::ModelCorrupt));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unpinned_model_needs_sha256() {
+        let err = expected_sha256(&unpinned(), None).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+        assert_eq!(expected_sha256(&unpinned(), Some(DIGEST)).unwrap(), DIGEST);
+    }
+
+    #[test]
+    fn given_sha256_must_match_pin() {
+        let pinned = KnownModel {
+            sha256: Some(DIGEST),
+            ..unpinned()
+        };
+        assert_eq!(expected_sha256(&pinned, None).unwrap(), DIGEST);
+        let other = DIGEST.replace('8', "9");
+        assert!(expected_sha256(&pinned, Some(&other)).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use clap::ValueEnum;
+
+use crate::transcribe::{Segment, SuccessOutput};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Plain transcript text
+    Text,
+    /// JSON object (the default, consumed by the Vocord plugin)
+    Json,
+    /// SubRip subtitles
+    Srt,
+    /// WebVTT subtitles
+    Vtt,
+}
+
+impl OutputFormat {
+    /// Subtitle formats are built from segment timestamps, so the engine
+    /// result must keep them even if `--segments` was not passed.
+    pub fn needs_segments(self) -> bool {
+        matches!(self, OutputFormat::Srt | OutputFormat::Vtt)
+    }
+}
+
+/// Render a transcription result for stdout. The returned string has no
+/// trailing newline; callers print it with `println!`.
+pub fn render(output: &SuccessOutput, format: OutputFormat) -> String {
+    let segments = output.segments.as_deref().unwrap_or(&[]);
+    match format {
+        OutputFormat::Text => output.text.trim().to_string(),
+        OutputFormat::Json => serde_json::to_string(output).expect("failed to serialize output"),
+        OutputFormat::Srt => render_srt(segments),
+        OutputFormat::Vtt => render_vtt(segments),
+    }
+}
+
+fn render_srt(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                timestamp(s.start, ','),
+                timestamp(s.end, ','),
+                s.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_vtt(segments: &[Segment]) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for s in segments {
+        out.push_str(&format!(
+            "\n{} --> {}\n{}\n",
+            timestamp(s.start, '.'),
+            timestamp(s.end, '.'),
+            s.text
+        ));
+    }
+    out
+}
+
+/// Format seconds as `HH:MM:SS<sep>mmm`. SRT uses a comma before the
+/// milliseconds, WebVTT a dot.
+fn timestamp(seconds: f32, sep: char) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let ms = total_ms % 1000;
+    let s = (total_ms / 1000) % 60;
+    let m = (total_ms / 60_000) % 60;
+    let h = total_ms / 3_600_000;
+    format!("{:02}:{:02}:{:02}{}{:03}", h, m, s, sep, ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(0.0, ','), "00:00:00,000");
+        assert_eq!(timestamp(61.5, ','), "00:01:01,500");
+        assert_eq!(timestamp(3723.042, '.'), "01:02:03.042");
+    }
+
+    #[test]
+    fn rounds_to_the_millisecond_and_clamps_negatives() {
+        assert_eq!(timestamp(59.9996, ','), "00:01:00,000");
+        assert_eq!(timestamp(-0.5, '.'), "00:00:00.000");
+    }
+}
@@ -0,0 +1,85 @@
+use serde::Serialize;
+
+use crate::transcribe::Segment;
+
+/// NDJSON events written to stderr, one per line: progress and segments with
+/// `--progress`, and warnings (GPU fallback, cache write failure) always, so
+/// stderr stays parseable.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Progress { percent: u32 },
+    Segment(&'a Segment),
+    GpuFallback { error: String },
+    CacheWriteFailed { error: String },
+}
+
+fn emit(event: &Event) {
+    eprintln!(
+        "{}",
+        serde_json::to_string(event).expect("failed to serialize progress event")
+    );
+}
+
+/// The GPU model load failed and the model is being loaded on the CPU.
+pub fn gpu_fallback(error: &dyn std::error::Error) {
+    emit(&Event::GpuFallback {
+        error: error.to_string(),
+    });
+}
+
+/// The transcript was produced but could not be stored in the cache.
+pub fn cache_write_failed(error: &dyn std::error::Error) {
+    emit(&Event::CacheWriteFailed {
+        error: error.to_string(),
+    });
+}
+
+/// Tracks how far decoding has got across every pass of a request (`--task
+/// both` decodes the audio twice), judged by the end of the latest segment.
+pub struct Progress {
+    /// Seconds of audio decoded per pass.
+    duration: f32,
+    passes: usize,
+    pass: usize,
+    percent: u32,
+}
+
+impl Progress {
+    pub fn new(duration: f32, passes: usize) -> Self {
+        emit(&Event::Progress { percent: 0 });
+        Self {
+            duration,
+            passes,
+            pass: 0,
+            percent: 0,
+        }
+    }
+
+    /// The current pass has decoded the audio up to `time` seconds.
+    pub fn reached(&mut self, time: f32) {
+        let fraction = if self.duration > 0.0 {
+            (time / self.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        self.update(((self.pass as f32 + fraction) / self.passes as f32 * 100.0) as u32);
+    }
+
+    pub fn finish_pass(&mut self) {
+        self.pass += 1;
+        self.update((self.pass * 100 / self.passes) as u32);
+    }
+
+    /// Segment times only move forward, but rounding could repeat a value.
+    fn update(&mut self, percent: u32) {
+        if percent > self.percent {
+            self.percent = percent;
+            emit(&Event::Progress { percent });
+        }
+    }
+
+    pub fn segment(&self, segment: &Segment) {
+        emit(&Event::Segment(segment));
+    }
+}
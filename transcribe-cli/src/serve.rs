@@ -0,0 +1,310 @@
+use std::path::PathBuf;
+
+use crate::cache::CacheArgs;
+use crate::transcribe::ModelArgs;
+
+#[cfg(unix)]
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use crate::error::{CliError, ErrorCode, ResultExt};
+#[cfg(unix)]
+use crate::transcribe::{self, ErrorOutput, SuccessOutput, TranscribeOptions, Transcriber};
+#[cfg(unix)]
+use crate::{audio, fetch};
+#[cfg(unix)]
+use serde::{Deserialize, Serialize};
+
+#[derive(clap::Args)]
+#[cfg_attr(not(unix), allow(dead_code))]
+pub struct ServeArgs {
+    /// Path of the Unix domain socket to listen on
+    #[arg(long)]
+    socket: PathBuf,
+
+    #[command(flatten)]
+    model: ModelArgs,
+
+    /// Unload the model after this many seconds without requests (0 = never)
+    #[arg(long, default_value_t = 300)]
+    idle_timeout: u64,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+}
+
+/// How often the accept loop wakes up to check for shutdown and idle unload.
+#[cfg(unix)]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A client that connects and then goes quiet must not block the daemon
+/// forever, since requests are served one connection at a time.
+#[cfg(unix)]
+const CLIENT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest `audio_bytes` payload accepted; voice messages are far smaller.
+#[cfg(unix)]
+const MAX_AUDIO_BYTES: u64 = 100 * 1024 * 1024;
+
+/// One line of newline-delimited JSON sent by a client. Either a control
+/// `command`, or the audio plus the same options the one-shot CLI takes. The
+/// audio is an `audio` path, `audio_bytes` bytes of WAV/Ogg sent right
+/// after the line, or a Discord CDN `url` to download it from.
+#[cfg(unix)]
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    command: Option<ControlCommand>,
+    #[serde(default)]
+    audio: Option<PathBuf>,
+    #[serde(default)]
+    audio_bytes: Option<u64>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(flatten)]
+    options: TranscribeOptions,
+}
+
+#[cfg(unix)]
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ControlCommand {
+    Ping,
+    Shutdown,
+}
+
+#[cfg(unix)]
+#[derive(Serialize)]
+struct AckOutput {
+    ok: bool,
+}
+
+#[cfg(unix)]
+struct Server {
+    transcriber: Transcriber,
+    idle_timeout: Option<Duration>,
+    last_used: Instant,
+}
+
+#[cfg(unix)]
+impl Server {
+    fn unload_if_idle(&mut self) {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return;
+        };
+        if self.transcriber.is_loaded() && self.last_used.elapsed() >= idle_timeout {
+            eprintln!(
+                "[serve] Idle for {}s, unloading model",
+                idle_timeout.as_secs()
+            );
+            self.transcriber.unload();
+        }
+    }
+
+    fn transcribe(
+        &mut self,
+        request: Request,
+        payload: &mut impl Read,
+    ) -> Result<SuccessOutput, Box<dyn std::error::Error>> {
+        // Read any payload first, even for a request about to be rejected,
+        // so the connection stays in sync.
+        let bytes = match request.audio_bytes {
+            Some(len) => Some(read_payload(payload, len)?),
+            None => None,
+        };
+        let samples = match (request.audio, bytes, request.url) {
+            (Some(audio), None, None) => transcribe::load_audio(&audio)?.samples,
+            (None, Some(bytes), None) => audio::decode_bytes(&bytes, "request payload")?.samples,
+            (None, None, Some(url)) => audio::decode_bytes(&fetch::fetch(&url)?, &url)?.samples,
+            (None, None, None) => {
+                return Err(CliError::new(
+                    ErrorCode::InvalidArgument,
+                    "Request is missing the 'audio', 'audio_bytes' or 'url' field",
+                )
+                .into())
+            }
+            _ => {
+                return Err(CliError::new(
+                    ErrorCode::InvalidArgument,
+                    "Request must set only one of 'audio', 'audio_bytes' and 'url'",
+                )
+                .into())
+            }
+        };
+
+        let result = self.transcriber.transcribe(samples, &request.options);
+        self.last_used = Instant::now();
+        result
+    }
+
+    fn respond(&mut self, line: &str, payload: &mut impl Read, shutdown: &AtomicBool) -> String {
+        let request = match serde_json::from_str::<Request>(line).code(ErrorCode::InvalidArgument) {
+            Ok(request) => request,
+            Err(e) => return to_line(&ErrorOutput::new(&e)),
+        };
+
+        match request.command {
+            Some(ControlCommand::Ping) => to_line(&AckOutput { ok: true }),
+            Some(ControlCommand::Shutdown) => {
+                shutdown.store(true, Ordering::SeqCst);
+                to_line(&AckOutput { ok: true })
+            }
+            None => match self.transcribe(request, payload) {
+                Ok(output) => to_line(&output),
+                Err(e) => to_line(&ErrorOutput::new(e.as_ref())),
+            },
+        }
+    }
+
+    fn handle_connection(&mut self, stream: UnixStream, shutdown: &AtomicBool) -> io::Result<()> {
+        // Accepted sockets inherit the listener's non-blocking flag on some
+        // platforms (macOS), which would make every read fail immediately.
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(CLIENT_READ_TIMEOUT))?;
+
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Any `audio_bytes` payload is read from the same buffered reader,
+            // right after its request line.
+            let response = self.respond(&line, &mut reader, shutdown);
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+
+            if shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read an `audio_bytes` payload. An oversized one is still drained so the
+/// connection stays in sync for the next request.
+#[cfg(unix)]
+fn read_payload(reader: &mut impl Read, len: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if len > MAX_AUDIO_BYTES {
+        io::copy(&mut reader.take(len), &mut io::sink())?;
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            format!("Audio payload exceeds {} bytes", MAX_AUDIO_BYTES),
+        )
+        .into());
+    }
+    let mut bytes = Vec::with_capacity(len as usize);
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            format!(
+                "Connection closed after {} of {} audio bytes",
+                bytes.len(),
+                len
+            ),
+        )
+        .into());
+    }
+    Ok(bytes)
+}
+
+#[cfg(unix)]
+fn to_line<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("failed to serialize response")
+}
+
+/// Bind the socket, replacing a stale socket file left by a crashed daemon
+/// but refusing to steal the path from one that is still running.
+#[cfg(unix)]
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("A daemon is already listening on {}", path.display()),
+            ));
+        }
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    // Requests name arbitrary files to read, so only our own user may connect.
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+#[cfg(unix)]
+pub fn run(args: ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&shutdown);
+    ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))?;
+
+    let mut server = Server {
+        transcriber: Transcriber::new(args.model, args.cache.open()),
+        idle_timeout: (args.idle_timeout > 0).then(|| Duration::from_secs(args.idle_timeout)),
+        last_used: Instant::now(),
+    };
+    // Load eagerly so a bad model fails at startup rather than on the first
+    // request, and so the first request is as fast as the following ones.
+    eprintln!(
+        "[serve] Loading model {}",
+        server.transcriber.model().display()
+    );
+    server.transcriber.engine()?;
+
+    let listener = bind(&args.socket)?;
+    listener.set_nonblocking(true)?;
+    eprintln!("[serve] Listening on {}", args.socket.display());
+
+    let result = loop {
+        if shutdown.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = server.handle_connection(stream, &shutdown) {
+                    eprintln!("[serve] Connection error: {}", e);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                server.unload_if_idle();
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => break Err(e.into()),
+        }
+    };
+
+    eprintln!("[serve] Shutting down");
+    let _ = fs::remove_file(&args.socket);
+    result
+}
+
+#[cfg(not(unix))]
+pub fn run(_args: ServeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    Err("serve mode requires Unix domain sockets and is not available on this platform".into())
+}
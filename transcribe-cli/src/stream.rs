@@ -0,0 +1,478 @@
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use serde::Serialize;
+
+use crate::audio::SAMPLE_RATE;
+use crate::engine::{self, Pass};
+use crate::error::{CliError, ErrorCode};
+use crate::transcribe::{ModelArgs, Segment, Task, TranscribeOptions};
+use crate::vad;
+
+#[derive(clap::Args)]
+pub struct StreamArgs {
+    #[command(flatten)]
+    model: ModelArgs,
+
+    /// Sample format of the raw 16 kHz mono PCM read from stdin
+    #[arg(long, value_enum, default_value = "s16le")]
+    pcm: PcmFormat,
+
+    /// Spoken language code (e.g. en, fr, es), or "auto" to let Whisper detect it
+    #[arg(long, default_value = "auto")]
+    language: String,
+
+    /// Transcribe in the spoken language or translate to English
+    #[arg(long, value_enum, default_value = "transcribe")]
+    task: Task,
+
+    /// Seconds of new audio between partial results
+    #[arg(long, default_value_t = 2.0)]
+    step: f32,
+
+    /// Longest stretch of audio re-decoded for partial results, in seconds;
+    /// older segments are finalized once it is reached
+    #[arg(long, default_value_t = 15.0)]
+    window: f32,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum PcmFormat {
+    /// Signed 16-bit little-endian
+    S16le,
+    /// 32-bit float little-endian
+    F32le,
+}
+
+impl PcmFormat {
+    fn sample_size(self) -> usize {
+        match self {
+            PcmFormat::S16le => 2,
+            PcmFormat::F32le => 4,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            PcmFormat::S16le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            PcmFormat::F32le => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+/// Lines written to stdout. A `partial` replaces the previous partial; a
+/// `final` segment never changes.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum StreamEvent {
+    Partial(Segment),
+    Final(Segment),
+}
+
+fn emit(event: &StreamEvent) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer(&mut stdout, event)?;
+    writeln!(stdout)?;
+    // Consumers render captions line by line; don't let them sit in a buffer.
+    stdout.flush()
+}
+
+/// Trailing silence that ends an utterance and finalizes it.
+const PAUSE: usize = SAMPLE_RATE as usize * 8 / 10;
+
+/// Less speech than this isn't worth a partial; Whisper tends to invent
+/// words for very short inputs.
+const MIN_PARTIAL: usize = SAMPLE_RATE as usize;
+
+/// Turns raw PCM bytes into samples. Reads don't respect sample
+/// boundaries, so a partial sample is held back until the next chunk.
+struct PcmDecoder {
+    format: PcmFormat,
+    leftover: Vec<u8>,
+}
+
+impl PcmDecoder {
+    fn new(format: PcmFormat) -> Self {
+        Self {
+            format,
+            leftover: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> Vec<f32> {
+        let size = self.format.sample_size();
+        self.leftover.extend_from_slice(bytes);
+        let whole = self.leftover.len() / size * size;
+        let samples = self.leftover[..whole]
+            .chunks_exact(size)
+            .map(|b| self.format.decode(b))
+            .collect();
+        self.leftover.drain(..whole);
+        samples
+    }
+}
+
+/// Read stdin on its own thread, so audio keeps flowing (and the recorder
+/// on the other end of the pipe never blocks) while inference runs.
+fn spawn_reader(format: PcmFormat) -> Receiver<io::Result<Vec<f32>>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buf = vec![0u8; 16 * 1024];
+        let mut decoder = PcmDecoder::new(format);
+        loop {
+            let n = match stdin.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            if tx.send(Ok(decoder.push(&buf[..n]))).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// Runs the model over a slice of audio; times in the result are relative
+/// to its start.
+type Decode = Box<dyn FnMut(Vec<f32>) -> Result<Pass, Box<dyn std::error::Error>>>;
+
+/// Audio not yet finalized, plus where it starts in the stream.
+struct Stream {
+    decode: Decode,
+    /// Longest stretch of pending speech decoded before finalizing, in samples.
+    window: usize,
+    /// Samples already finalized or dropped and removed from `pending`.
+    offset: usize,
+    pending: Vec<f32>,
+}
+
+impl Stream {
+    fn seconds(&self, index: usize) -> f32 {
+        (self.offset + index) as f32 / SAMPLE_RATE as f32
+    }
+
+    fn drop_pending(&mut self, samples: usize) {
+        let samples = samples.min(self.pending.len());
+        self.pending.drain(..samples);
+        self.offset += samples;
+    }
+
+    /// Decode part of the pending audio into segments with absolute times.
+    fn infer(&mut self, range: Range<usize>) -> Result<Vec<Segment>, Box<dyn std::error::Error>> {
+        let start = range.start;
+        let end = range.end;
+        let pass = (self.decode)(self.pending[range].to_vec())?;
+        let base = self.seconds(start);
+        let mut segments: Vec<Segment> = pass
+            .segments
+            .into_iter()
+            .map(|s| Segment {
+                start: base + s.start,
+                end: base + s.end,
+                ..s
+            })
+            .filter(|s| !s.text.is_empty())
+            .collect();
+        // Engines without timestamps get one segment for the whole range.
+        if segments.is_empty() && !pass.text.trim().is_empty() {
+            segments.push(Segment {
+                start: base,
+                end: self.seconds(end),
+                text: pass.text.trim().to_string(),
+                words: Vec::new(),
+            });
+        }
+        Ok(segments)
+    }
+
+    /// Decode the pending audio and return whatever it settles.
+    fn update(&mut self, eof: bool) -> Result<Vec<StreamEvent>, Box<dyn std::error::Error>> {
+        let regions = vad::detect(&self.pending);
+        let (Some(first), Some(last)) = (regions.first(), regions.last()) else {
+            // Nothing but silence so far: no point decoding it. Keep a little
+            // so the onset of the next word survives.
+            let keep = if eof { 0 } else { PAUSE };
+            self.drop_pending(self.pending.len().saturating_sub(keep));
+            return Ok(Vec::new());
+        };
+        let (speech_start, speech_end) = (first.start, last.end);
+
+        let paused = self.pending.len() - speech_end >= PAUSE;
+        if eof || paused {
+            // The utterance is over: everything in it is final.
+            let segments = self.infer(speech_start..speech_end)?;
+            self.drop_pending(speech_end);
+            return Ok(segments.into_iter().map(StreamEvent::Final).collect());
+        }
+
+        let window = self.window;
+        let end = self.pending.len();
+        if end - speech_start < MIN_PARTIAL {
+            return Ok(Vec::new());
+        }
+        let mut segments = self.infer(speech_start..end)?;
+
+        if end - speech_start >= window && segments.len() > 1 {
+            // The window is full: finalize all but the last segment, which
+            // may have been cut off mid-word, and restart from it.
+            let last = segments.pop().unwrap();
+            let restart = ((last.start - self.seconds(0)) * SAMPLE_RATE as f32) as usize;
+            self.drop_pending(restart.max(speech_start));
+            let mut events: Vec<_> = segments.into_iter().map(StreamEvent::Final).collect();
+            events.push(StreamEvent::Partial(last));
+            Ok(events)
+        } else if end - speech_start >= window {
+            // One segment spanning the whole window: no safe place to cut, so
+            // finalize it all rather than let the window grow without bound.
+            self.drop_pending(end);
+            Ok(segments.into_iter().map(StreamEvent::Final).collect())
+        } else if !segments.is_empty() {
+            let partial = Segment {
+                start: segments[0].start,
+                end: segments[segments.len() - 1].end,
+                text: segments
+                    .iter()
+                    .map(|s| s.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                words: Vec::new(),
+            };
+            Ok(vec![StreamEvent::Partial(partial)])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
+
+pub fn run(args: StreamArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let options = TranscribeOptions {
+        language: args.language,
+        task: args.task,
+        ..Default::default()
+    };
+    if options.task == Task::Both {
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            "Stream mode supports --task transcribe or translate, not both",
+        )
+        .into());
+    }
+    if !(args.step > 0.0 && args.window >= args.step) {
+        return Err(CliError::new(
+            ErrorCode::InvalidArgument,
+            "--step must be positive and no longer than --window",
+        )
+        .into());
+    }
+    engine::check_options(args.model.engine, &options)?;
+
+    let step = (args.step * SAMPLE_RATE as f32) as usize;
+    let mut engine = args.model.load()?;
+    let translate = options.task == Task::Translate;
+    let mut stream = Stream {
+        decode: Box::new(move |samples| {
+            engine
+                .backend
+                .transcribe(samples, &options, translate, None)
+        }),
+        window: (args.window * SAMPLE_RATE as f32) as usize,
+        offset: 0,
+        pending: Vec::new(),
+    };
+    eprintln!("[stream] Model loaded, reading PCM from stdin");
+
+    let input = spawn_reader(args.pcm);
+    let mut fresh = 0;
+    loop {
+        // Block for the next chunk, then take everything else already
+        // queued: after a slow decode, catch up in one pass.
+        let mut eof = false;
+        match input.recv() {
+            Ok(chunk) => {
+                let chunk = chunk?;
+                fresh += chunk.len();
+                stream.pending.extend(chunk);
+            }
+            Err(_) => eof = true,
+        }
+        while !eof {
+            match input.try_recv() {
+                Ok(chunk) => {
+                    let chunk = chunk?;
+                    fresh += chunk.len();
+                    stream.pending.extend(chunk);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => eof = true,
+            }
+        }
+
+        if eof {
+            for event in stream.update(true)? {
+                emit(&event)?;
+            }
+            return Ok(());
+        }
+        if fresh >= step {
+            fresh = 0;
+            for event in stream.update(false)? {
+                emit(&event)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn silence(seconds: f32) -> Vec<f32> {
+        vec![0.0; (seconds * SAMPLE_RATE as f32) as usize]
+    }
+
+    fn tone(seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| 0.5 * (i as f32 * 440.0 * std::f32::consts::TAU / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    /// A stream whose model always returns `segments`, relative to the
+    /// start of whatever it is given.
+    fn stream(window: f32, segments: &[(f32, f32, &str)]) -> Stream {
+        let segments: Vec<_> = segments
+            .iter()
+            .map(|&(start, end, text)| (start, end, text.to_string()))
+            .collect();
+        Stream {
+            decode: Box::new(move |_| {
+                Ok(Pass {
+                    text: String::new(),
+                    segments: segments
+                        .iter()
+                        .map(|(start, end, text)| Segment {
+                            start: *start,
+                            end: *end,
+                            text: text.clone(),
+                            words: Vec::new(),
+                        })
+                        .collect(),
+                    language: None,
+                })
+            }),
+            window: (window * SAMPLE_RATE as f32) as usize,
+            offset: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    fn texts(events: &[StreamEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                StreamEvent::Partial(s) => format!("partial {}", s.text),
+                StreamEvent::Final(s) => format!("final {}", s.text),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pause_finalizes_the_utterance() {
+        let mut stream = stream(15.0, &[(0.0, 2.0, "Hello.")]);
+        stream.pending.extend(silence(0.5));
+        stream.pending.extend(tone(2.0));
+        stream.pending.extend(silence(1.5));
+
+        let events = stream.update(false).unwrap();
+        assert_eq!(texts(&events), ["final Hello."]);
+        let StreamEvent::Final(segment) = &events[0] else {
+            unreachable!()
+        };
+        // Times are shifted by where the speech (less VAD padding) starts.
+        assert!((segment.start - 0.3).abs() < 0.05, "{}", segment.start);
+        assert!((segment.end - 2.3).abs() < 0.05, "{}", segment.end);
+        // Everything up to the end of the speech is gone; the pause stays.
+        assert!(stream.offset > 2 * SAMPLE_RATE as usize);
+        assert_eq!(
+            stream.offset + stream.pending.len(),
+            4 * SAMPLE_RATE as usize
+        );
+    }
+
+    #[test]
+    fn ongoing_speech_is_one_partial() {
+        let mut stream = stream(15.0, &[(0.0, 1.5, "one"), (1.5, 3.0, "two")]);
+        stream.pending.extend(tone(3.0));
+
+        let events = stream.update(false).unwrap();
+        assert_eq!(texts(&events), ["partial one two"]);
+        assert_eq!(stream.offset, 0);
+    }
+
+    #[test]
+    fn short_speech_is_not_decoded() {
+        let mut stream = stream(15.0, &[]);
+        stream.decode = Box::new(|_| panic!("decoded less than MIN_PARTIAL"));
+        stream.pending.extend(tone(0.5));
+
+        assert!(stream.update(false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn full_window_finalizes_all_but_the_last_segment() {
+        let mut stream = stream(2.0, &[(0.0, 1.5, "one"), (1.5, 3.0, "two")]);
+        stream.pending.extend(tone(3.0));
+
+        let events = stream.update(false).unwrap();
+        assert_eq!(texts(&events), ["final one", "partial two"]);
+        // Decoding restarts where the last segment began.
+        assert_eq!(stream.offset, 24000);
+    }
+
+    #[test]
+    fn full_window_with_one_segment_finalizes_it() {
+        let mut stream = stream(2.0, &[(0.0, 3.0, "everything")]);
+        stream.pending.extend(tone(3.0));
+
+        let events = stream.update(false).unwrap();
+        assert_eq!(texts(&events), ["final everything"]);
+        assert!(stream.pending.is_empty());
+    }
+
+    #[test]
+    fn eof_drains_silence() {
+        let mut stream = stream(15.0, &[]);
+        stream.pending.extend(silence(2.0));
+
+        assert!(stream.update(false).unwrap().is_empty());
+        assert_eq!(stream.pending.len(), PAUSE);
+        assert!(stream.update(true).unwrap().is_empty());
+        assert!(stream.pending.is_empty());
+    }
+
+    #[test]
+    fn decoder_keeps_partial_samples_for_the_next_chunk() {
+        let mut decoder = PcmDecoder::new(PcmFormat::S16le);
+        let bytes = [0x00, 0x40, 0x00, 0xc0];
+        assert_eq!(decoder.push(&bytes[..1]), Vec::<f32>::new());
+        assert_eq!(decoder.push(&bytes[1..3]), [0.5]);
+        assert_eq!(decoder.push(&bytes[3..]), [-0.5]);
+
+        let mut decoder = PcmDecoder::new(PcmFormat::F32le);
+        let bytes: Vec<u8> = [0.25f32, -1.0]
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect();
+        assert_eq!(decoder.push(&bytes[..3]), Vec::<f32>::new());
+        assert_eq!(decoder.push(&bytes[3..6]), [0.25]);
+        assert_eq!(decoder.push(&bytes[6..]), [-1.0]);
+        assert!(decoder.leftover.is_empty());
+    }
+}
@@ -0,0 +1,614 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{Decoded, SourceFormat};
+use crate::cache::Cache;
+use crate::engine::{self, Backend, EngineKind, Pass};
+use crate::error::{CliError, ErrorCode};
+use crate::filter::{self, Warning};
+use crate::progress::{self, Progress};
+use crate::{audio, ggml, models, vad};
+
+/// Per-request inference options, shared by the one-shot CLI and the
+/// `serve` daemon (where they are read from each JSON request line).
+#[derive(clap::Args, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TranscribeOptions {
+    /// Spoken language code (e.g. en, fr, es), or "auto" to let Whisper detect it
+    #[arg(long, default_value = "auto")]
+    pub language: String,
+
+    /// Include per-segment start/end timestamps in the JSON output
+    #[arg(long)]
+    pub segments: bool,
+
+    /// Include per-word start/end timestamps and token probabilities in the
+    /// JSON output (whisper only)
+    #[arg(long)]
+    pub words: bool,
+
+    /// Include the probabilities of the N most likely spoken languages in
+    /// the JSON output (whisper only)
+    #[arg(long, value_name = "N")]
+    pub language_probabilities: Option<usize>,
+
+    /// Wrap words whose confidence is below this value (0-1) in `--marker`
+    /// in the text, flagging them for a second look (whisper only)
+    #[arg(long, value_name = "CONFIDENCE")]
+    pub mark_below: Option<f32>,
+
+    /// How `--mark-below` marks a word: `{}` stands for the word
+    #[arg(long, default_value = "[?{}]")]
+    pub marker: String,
+
+    /// Skip silence before inference using voice activity detection
+    #[arg(long)]
+    pub vad: bool,
+
+    /// Transcribe in the spoken language, translate to English, or both
+    #[arg(long, value_enum, default_value = "transcribe")]
+    pub task: Task,
+
+    /// Remove suspected hallucinations (loops, stock phrases, text over
+    /// silence) from the transcript, rather than only reporting them in
+    /// `warnings`
+    #[arg(long)]
+    pub remove_hallucinations: bool,
+}
+
+impl TranscribeOptions {
+    /// Whether decoding has to produce words: for the output, or to mark
+    /// uncertain ones in the text.
+    pub fn needs_words(&self) -> bool {
+        self.words || self.mark_below.is_some()
+    }
+}
+
+impl Default for TranscribeOptions {
+    fn default() -> Self {
+        Self {
+            language: "auto".to_string(),
+            segments: false,
+            words: false,
+            language_probabilities: None,
+            mark_below: None,
+            marker: "[?{}]".to_string(),
+            vad: false,
+            task: Task::Transcribe,
+            remove_hallucinations: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Task {
+    /// Text in the spoken language
+    Transcribe,
+    /// English translation of the speech
+    Translate,
+    /// Original transcript in `text` plus the English translation in `translation`
+    Both,
+}
+
+/// Where the model runs. `Auto` is only ever requested; the device reported
+/// in the output is the one the model was actually loaded on.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Device {
+    /// Try the GPU, and retry on the CPU if loading fails
+    Auto,
+    Cpu,
+    Gpu,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SuccessOutput {
+    pub text: String,
+    pub device: Device,
+    /// The spoken language: the one Whisper detected, or the one requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// The most likely spoken languages with their probabilities, with
+    /// `language_probabilities`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language_probabilities: Option<BTreeMap<String, f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<Segment>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub words: Option<Vec<Word>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<String>,
+    /// Set when VAD found no speech at all, in which case `text` is empty.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_speech: bool,
+    /// Set when the result came from the transcript cache. `device` is then
+    /// the device of the run that produced the cached entry.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    /// Suspected hallucinations, also removed from the output when
+    /// `remove_hallucinations` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Warning>,
+    /// The input's original format, reported with `--verbose`. Set per
+    /// input after transcription, so it is never read back from the cache.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<SourceFormat>,
+}
+
+/// A span of the transcript, with times in seconds from the start of the audio.
+#[derive(Serialize, Deserialize)]
+pub struct Segment {
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+    /// Filled in with `--words`, and reported flattened into the output's
+    /// own `words` list once filtering has dropped the segments it removes.
+    #[serde(skip)]
+    pub words: Vec<Word>,
+}
+
+/// A word of the transcript, with times in seconds from the start of the audio.
+#[derive(Serialize, Deserialize)]
+pub struct Word {
+    pub word: String,
+    pub start: f32,
+    pub end: f32,
+    /// Mean probability of the word's tokens.
+    pub probability: f32,
+    /// The exponent of the tokens' mean log-probability, i.e. their
+    /// geometric mean probability: one unlikely token drags it down more
+    /// than it does `probability`.
+    pub confidence: f32,
+    pub tokens: Vec<Token>,
+}
+
+/// One Whisper token of a word. A character split across tokens shows up
+/// as U+FFFD in each token's `text`, but whole in the word.
+#[derive(Serialize, Deserialize)]
+pub struct Token {
+    pub text: String,
+    pub probability: f32,
+    pub logprob: f32,
+}
+
+#[derive(Serialize)]
+pub struct ErrorOutput {
+    pub error: String,
+    pub code: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+}
+
+impl ErrorOutput {
+    pub fn new(error: &(dyn std::error::Error + 'static)) -> Self {
+        let code = error
+            .downcast_ref::<CliError>()
+            .map_or(ErrorCode::Internal, |e| e.code);
+        Self {
+            error: error.to_string(),
+            code,
+            hint: code.hint(),
+        }
+    }
+}
+
+/// Which model to load and where, shared by every command that runs one.
+#[derive(clap::Args, Clone)]
+pub struct ModelArgs {
+    /// Path to the model: a GGML file for whisper, a model directory for
+    /// parakeet and moonshine
+    #[arg(long, required = true)]
+    pub model: Option<PathBuf>,
+
+    /// Transcription engine; parakeet and moonshine are English-only and
+    /// must be enabled as cargo features
+    #[arg(long, value_enum, default_value = "whisper")]
+    pub engine: EngineKind,
+
+    /// Device to run the model on; "auto" falls back to the CPU if the GPU fails
+    #[arg(long, value_enum, default_value = "auto")]
+    pub device: Device,
+
+    /// Hash a Whisper model before loading it and check it against the
+    /// <model>.sha256 sidecar written by `models download`; adds a few
+    /// seconds for the large models
+    #[arg(long)]
+    pub verify_model: bool,
+}
+
+impl ModelArgs {
+    /// The model path. It is only absent from the top-level arguments when a
+    /// subcommand is given, and then these are never used.
+    pub fn path(&self) -> &Path {
+        self.model.as_deref().expect("--model is required")
+    }
+
+    /// Load the model, checking the path upfront to produce an actionable
+    /// error message before the engine emits an opaque C-level one.
+    pub fn load(&self) -> Result<LoadedEngine, Box<dyn std::error::Error>> {
+        let model = self.path();
+        if !model.exists() {
+            return Err(CliError::new(
+                ErrorCode::ModelNotFound,
+                format!("Model file not found: {}", model.display()),
+            )
+            .into());
+        }
+        if self.verify_model && self.engine != EngineKind::Whisper {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                "--verify-model only checks Whisper GGML models",
+            )
+            .into());
+        }
+        // Catch truncated or damaged models before whisper.cpp sees them. This
+        // reads the headers only; hashing a multi-gigabyte model on every load
+        // is opt-in.
+        if self.engine == EngineKind::Whisper {
+            ggml::validate(model)?;
+            if self.verify_model {
+                models::verify_file(model, None)?;
+            }
+        }
+
+        let (backend, device) = Backend::load(self.engine, model, self.device)?;
+        Ok(LoadedEngine { backend, device })
+    }
+}
+
+/// A loaded model together with the device it ended up on.
+pub struct LoadedEngine {
+    pub backend: Backend,
+    pub device: Device,
+}
+
+impl LoadedEngine {
+    pub fn unload(mut self) {
+        self.backend.unload();
+    }
+}
+
+/// Check that the audio file exists and decode it to engine samples. A path
+/// of `-` reads a whole WAV or Ogg file from stdin instead.
+pub fn load_audio(path: &Path) -> Result<Decoded, Box<dyn std::error::Error>> {
+    if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        return audio::decode_bytes(&bytes, "stdin");
+    }
+    if !path.exists() {
+        return Err(CliError::new(
+            ErrorCode::AudioNotFound,
+            format!("Audio file not found: {}", path.display()),
+        )
+        .into());
+    }
+    audio::load(path)
+}
+
+/// Run one decoding pass, with segment and word times mapped through
+/// `to_original`. `on_segment` sees each segment as soon as the engine
+/// produces it, with times still on the engine's timeline.
+fn decode(
+    engine: &mut LoadedEngine,
+    samples: Vec<f32>,
+    options: &TranscribeOptions,
+    translate: bool,
+    to_original: &dyn Fn(f32) -> f32,
+    on_segment: Option<&mut dyn FnMut(&Segment)>,
+) -> Result<Pass, Box<dyn std::error::Error>> {
+    let mut pass = engine
+        .backend
+        .transcribe(samples, options, translate, on_segment)?;
+    for segment in &mut pass.segments {
+        segment.start = to_original(segment.start);
+        segment.end = to_original(segment.end);
+        for word in &mut segment.words {
+            word.start = to_original(word.start);
+            word.end = to_original(word.end);
+        }
+    }
+    Ok(pass)
+}
+
+/// Wrap the words with a confidence below `threshold` in `marker`, in place:
+/// everything else in the text, spacing included, is kept as it is, so
+/// languages written without spaces come out intact. A word not found in
+/// the rest of the text is left unmarked.
+fn mark_uncertain(text: &str, words: &[Word], threshold: f32, marker: &str) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut rest = text;
+    for word in words {
+        let Some(at) = rest.find(&word.word) else {
+            continue;
+        };
+        marked.push_str(&rest[..at]);
+        if word.confidence < threshold {
+            marked.push_str(&marker.replace("{}", &word.word));
+        } else {
+            marked.push_str(&word.word);
+        }
+        rest = &rest[at + word.word.len()..];
+    }
+    marked.push_str(rest);
+    marked
+}
+
+pub fn transcribe(
+    engine: &mut LoadedEngine,
+    samples: Vec<f32>,
+    options: &TranscribeOptions,
+    report_progress: bool,
+) -> Result<SuccessOutput, Box<dyn std::error::Error>> {
+    // Only speech is sent to the engine: Whisper tends to invent text on
+    // silent stretches, and skipping them also saves inference time.
+    let mut compacted = None;
+    let mut speech = None;
+    let samples = if options.vad {
+        let regions = vad::detect(&samples);
+        if regions.is_empty() {
+            return Ok(SuccessOutput {
+                text: String::new(),
+                device: engine.device,
+                language: None,
+                language_probabilities: None,
+                segments: options.segments.then(Vec::new),
+                words: options.words.then(Vec::new),
+                translation: (options.task == Task::Both).then(String::new),
+                no_speech: true,
+                cached: false,
+                warnings: Vec::new(),
+                audio: None,
+            });
+        }
+        let mut speech = vad::compact(&samples, &regions);
+        let speech_samples = std::mem::take(&mut speech.samples);
+        compacted = Some(speech);
+        speech_samples
+    } else {
+        // Without VAD, silent stretches reach the engine; knowing where the
+        // speech is lets the filter catch text invented over them.
+        let to_seconds = |i: usize| i as f32 / audio::SAMPLE_RATE as f32;
+        speech = Some(
+            vad::detect(&samples)
+                .into_iter()
+                .map(|r| to_seconds(r.start)..to_seconds(r.end))
+                .collect::<Vec<_>>(),
+        );
+        samples
+    };
+    let to_original = |t: f32| compacted.as_ref().map_or(t, |c| c.to_original(t));
+
+    let language_probabilities = match options.language_probabilities {
+        Some(n) => {
+            let mut ranked = engine.backend.detect_language(&samples)?;
+            ranked.truncate(n);
+            Some(
+                ranked
+                    .into_iter()
+                    .map(|(language, p)| (language.to_string(), p))
+                    .collect(),
+            )
+        }
+        None => None,
+    };
+
+    let remove = options.remove_hallucinations;
+    let passes = if options.task == Task::Both { 2 } else { 1 };
+    let duration = samples.len() as f32 / audio::SAMPLE_RATE as f32;
+    let mut progress = report_progress.then(|| Progress::new(duration, passes));
+    let mut run_pass = |samples: Vec<f32>, translate: bool, report_segments: bool| {
+        let Some(progress) = progress.as_mut() else {
+            return decode(engine, samples, options, translate, &to_original, None);
+        };
+        let mut on_segment = |segment: &Segment| {
+            progress.reached(segment.end);
+            if !report_segments {
+                return;
+            }
+            let mut segment = Segment {
+                start: to_original(segment.start),
+                end: to_original(segment.end),
+                text: segment.text.clone(),
+                words: Vec::new(),
+            };
+            // The output goes through the same check, so never announce a
+            // segment it is going to drop.
+            if filter::check_segment(&mut segment, speech.as_deref(), remove).0 {
+                progress.segment(&segment);
+            }
+        };
+        let pass = decode(
+            engine,
+            samples,
+            options,
+            translate,
+            &to_original,
+            Some(&mut on_segment as &mut dyn FnMut(&Segment)),
+        );
+        progress.finish_pass();
+        pass
+    };
+
+    // Whisper produces one language per decoding pass, so `both` runs the
+    // translation as a second pass over the same samples.
+    let translation_samples = (options.task == Task::Both).then(|| samples.clone());
+    let Pass {
+        mut text,
+        mut segments,
+        language,
+    } = run_pass(samples, options.task == Task::Translate, true)?;
+    let mut translation = match translation_samples {
+        Some(samples) => Some(run_pass(samples, true, false)?.text.trim().to_string()),
+        None => None,
+    };
+
+    let mut warnings = filter::apply(&mut text, &mut segments, speech.as_deref(), remove);
+    if let Some(translation) = translation.as_mut() {
+        warnings.extend(filter::collapse_text(translation, remove));
+    }
+    let mut words: Vec<Word> = segments
+        .iter_mut()
+        .flat_map(|s| std::mem::take(&mut s.words))
+        .collect();
+    if remove {
+        // Loops can span segments; the text's were collapsed above.
+        filter::collapse_words(&mut words);
+    }
+    if let Some(threshold) = options.mark_below {
+        text = mark_uncertain(&text, &words, threshold, &options.marker);
+    }
+
+    Ok(SuccessOutput {
+        text,
+        device: engine.device,
+        language,
+        language_probabilities,
+        segments: options.segments.then_some(segments),
+        words: options.words.then_some(words),
+        translation,
+        no_speech: false,
+        cached: false,
+        warnings,
+        audio: None,
+    })
+}
+
+/// Owns the model, loaded on first use so cache hits never pay for it, and
+/// the optional transcript cache. Shared by every entry point.
+pub struct Transcriber {
+    model: ModelArgs,
+    engine: Option<LoadedEngine>,
+    cache: Option<Cache>,
+    progress: bool,
+}
+
+impl Transcriber {
+    pub fn new(model: ModelArgs, cache: Option<Cache>) -> Self {
+        Self {
+            model,
+            engine: None,
+            cache,
+            progress: false,
+        }
+    }
+
+    /// Write NDJSON progress events to stderr while transcribing.
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn model(&self) -> &Path {
+        self.model.path()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.engine.is_some()
+    }
+
+    pub fn has_cache(&self) -> bool {
+        self.cache.is_some()
+    }
+
+    pub fn engine(&mut self) -> Result<&mut LoadedEngine, Box<dyn std::error::Error>> {
+        if self.engine.is_none() {
+            self.engine = Some(self.model.load()?);
+        }
+        Ok(self.engine.as_mut().expect("engine was just loaded"))
+    }
+
+    pub fn unload(&mut self) {
+        if let Some(engine) = self.engine.take() {
+            engine.unload();
+        }
+    }
+
+    pub fn transcribe(
+        &mut self,
+        samples: Vec<f32>,
+        options: &TranscribeOptions,
+    ) -> Result<SuccessOutput, Box<dyn std::error::Error>> {
+        engine::check_options(self.model.engine, options)?;
+
+        let key = match &self.cache {
+            Some(_) => Some(Cache::key(&samples, self.model.path(), options)?),
+            None => None,
+        };
+        if let (Some(cache), Some(key)) = (&self.cache, &key) {
+            if let Some(mut output) = cache.get(key) {
+                output.cached = true;
+                return Ok(output);
+            }
+        }
+
+        let progress = self.progress;
+        let output = transcribe(self.engine()?, samples, options, progress)?;
+
+        if let (Some(cache), Some(key)) = (&self.cache, &key) {
+            // A cache write failure must not fail a successful transcription.
+            if let Err(e) = cache.put(key, &output) {
+                progress::cache_write_failed(&e);
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[(&str, f32)]) -> Vec<Word> {
+        words
+            .iter()
+            .map(|&(word, confidence)| Word {
+                word: word.to_string(),
+                start: 0.0,
+                end: 0.0,
+                probability: confidence,
+                confidence,
+                tokens: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn marks_words_in_place() {
+        let text = "Meet me at Päivölä, okay?";
+        let words = words(&[
+            ("Meet", 0.9),
+            ("me", 0.9),
+            ("at", 0.8),
+            ("Päivölä,", 0.2),
+            ("okay?", 0.7),
+        ]);
+        assert_eq!(
+            mark_uncertain(text, &words, 0.5, "[?{}]"),
+            "Meet me at [?Päivölä,] okay?"
+        );
+    }
+
+    #[test]
+    fn keeps_text_without_spaces_intact() {
+        let text = "我明天去北京。";
+        let words = words(&[("我", 0.9), ("明天", 0.3), ("去", 0.9), ("北京。", 0.9)]);
+        assert_eq!(
+            mark_uncertain(text, &words, 0.5, "[?{}]"),
+            "我[?明天]去北京。"
+        );
+        // Marking nothing returns the text unchanged, spacing and all.
+        assert_eq!(mark_uncertain(text, &words, 0.1, "[?{}]"), text);
+    }
+
+    #[test]
+    fn skips_words_missing_from_text() {
+        let text = "hello there";
+        let words = words(&[("hello", 0.9), ("again", 0.1), ("there", 0.1)]);
+        assert_eq!(mark_uncertain(text, &words, 0.5, "*{}*"), "hello *there*");
+    }
+}
@@ -0,0 +1,148 @@
+use std::ops::Range;
+
+use crate::audio::SAMPLE_RATE;
+
+/// Analysis frame length. 30 ms is short enough to find word boundaries and
+/// long enough for a stable energy estimate.
+const FRAME_LEN: usize = ms_to_samples(30);
+
+/// Frames must be this far above the estimated noise floor to count as speech.
+const SNR_DB: f32 = 12.0;
+
+/// Bounds on the adaptive threshold: nothing quieter than the lower bound is
+/// ever speech, and anything louder than the upper bound always is (so a clip
+/// that is speech from start to finish doesn't raise the floor past it).
+const MIN_THRESHOLD_DB: f32 = -45.0;
+const MAX_THRESHOLD_DB: f32 = -30.0;
+
+/// Context kept around each speech region so word onsets and trailing
+/// consonants are not clipped.
+const PAD: usize = ms_to_samples(200);
+
+/// Regions separated by less than this are merged; short pauses between
+/// words must not split a sentence.
+const MERGE_GAP: usize = ms_to_samples(500);
+
+/// Isolated bursts shorter than this (clicks, bumps) are dropped.
+const MIN_REGION: usize = ms_to_samples(150);
+
+const fn ms_to_samples(ms: usize) -> usize {
+    SAMPLE_RATE as usize * ms / 1000
+}
+
+/// Find the speech regions of a 16 kHz mono clip, as sample ranges in the
+/// original timeline. Returns an empty list when the clip has no speech.
+pub fn detect(samples: &[f32]) -> Vec<Range<usize>> {
+    let energies: Vec<f32> = samples.chunks(FRAME_LEN).map(frame_db).collect();
+    if energies.is_empty() {
+        return Vec::new();
+    }
+
+    // The 10th percentile of frame energies approximates the background level.
+    let mut sorted = energies.clone();
+    sorted.sort_by(f32::total_cmp);
+    let noise_floor = sorted[sorted.len() / 10];
+    let threshold = (noise_floor + SNR_DB).clamp(MIN_THRESHOLD_DB, MAX_THRESHOLD_DB);
+
+    let mut cores: Vec<Range<usize>> = Vec::new();
+    for (i, &db) in energies.iter().enumerate() {
+        if db < threshold {
+            continue;
+        }
+        let start = i * FRAME_LEN;
+        let end = ((i + 1) * FRAME_LEN).min(samples.len());
+        match cores.last_mut() {
+            Some(last) if start <= last.end + MERGE_GAP => last.end = end,
+            _ => cores.push(start..end),
+        }
+    }
+    cores.retain(|r| r.len() >= MIN_REGION);
+
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for core in cores {
+        let start = core.start.saturating_sub(PAD);
+        let end = (core.end + PAD).min(samples.len());
+        match regions.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => regions.push(start..end),
+        }
+    }
+    regions
+}
+
+fn frame_db(frame: &[f32]) -> f32 {
+    let power = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+    10.0 * (power + 1e-10).log10()
+}
+
+/// The speech-only audio sent to the engine, and how to map times in it back
+/// to the original clip.
+pub struct Compacted {
+    pub samples: Vec<f32>,
+    /// `(original range, start offset in the compacted samples)` per region.
+    regions: Vec<(Range<usize>, usize)>,
+}
+
+/// Concatenate the speech regions, dropping the silence between them.
+pub fn compact(samples: &[f32], regions: &[Range<usize>]) -> Compacted {
+    let mut out = Vec::with_capacity(regions.iter().map(|r| r.len()).sum());
+    let mut mapped = Vec::with_capacity(regions.len());
+    for region in regions {
+        mapped.push((region.clone(), out.len()));
+        out.extend_from_slice(&samples[region.clone()]);
+    }
+    Compacted {
+        samples: out,
+        regions: mapped,
+    }
+}
+
+impl Compacted {
+    /// Convert a time in seconds on the compacted timeline to the original one.
+    pub fn to_original(&self, seconds: f32) -> f32 {
+        let position = (seconds.max(0.0) * SAMPLE_RATE as f32) as usize;
+        let (range, offset) = self
+            .regions
+            .iter()
+            .find(|(range, offset)| position <= offset + range.len())
+            .or(self.regions.last())
+            .expect("compacted audio has at least one region");
+        let within = (position - offset).min(range.len());
+        (range.start + within) as f32 / SAMPLE_RATE as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Six seconds of audio with speech from 1-2 s and 4-5 s.
+    fn compacted() -> Compacted {
+        let second = SAMPLE_RATE as usize;
+        compact(
+            &vec![0.0; 6 * second],
+            &[second..2 * second, 4 * second..5 * second],
+        )
+    }
+
+    #[test]
+    fn maps_times_into_their_region() {
+        let c = compacted();
+        assert_eq!(c.samples.len(), 2 * SAMPLE_RATE as usize);
+        assert_eq!(c.to_original(0.0), 1.0);
+        assert_eq!(c.to_original(0.5), 1.5);
+        assert_eq!(c.to_original(1.5), 4.5);
+    }
+
+    #[test]
+    fn boundary_maps_to_end_of_earlier_region() {
+        assert_eq!(compacted().to_original(1.0), 2.0);
+    }
+
+    #[test]
+    fn clamps_times_outside_the_audio() {
+        let c = compacted();
+        assert_eq!(c.to_original(-1.0), 1.0);
+        assert_eq!(c.to_original(3.0), 5.0);
+    }
+}
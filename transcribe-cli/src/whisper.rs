@@ -0,0 +1,242 @@
+use std::ffi::c_int;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use whisper_rs::{
+    FullParams, SamplingStrategy, SegmentCallbackData, WhisperContext, WhisperContextParameters,
+    WhisperState,
+};
+
+use crate::audio::SAMPLE_RATE;
+use crate::engine::Pass;
+use crate::transcribe::{Segment, Token, Word};
+
+/// whisper.cpp driven through whisper-rs directly, in place of transcribe-rs's
+/// `WhisperEngine`, which reduces its result to segment text and times.
+/// Holding the decoder state ourselves also gives the tokens, with their
+/// timestamps and probabilities. whisper-rs is pinned in Cargo.toml to the
+/// version transcribe-rs links, so there is only one whisper.cpp.
+pub struct WhisperModel {
+    context: WhisperContext,
+    state: WhisperState,
+}
+
+/// Whisper identifies the language from a single 30 second window.
+const DETECT_SAMPLES: usize = 30 * SAMPLE_RATE as usize;
+
+/// How often to check whether decoding has finished while waiting for its
+/// segments.
+const POLL: Duration = Duration::from_millis(100);
+
+/// whisper.cpp's own default thread count.
+fn threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get().min(4))
+}
+
+/// Whisper timestamps count centiseconds.
+fn seconds(t: i64) -> f32 {
+    t as f32 / 100.0
+}
+
+/// Whether a token starts with a character of a script written without
+/// spaces between words (Chinese, Japanese, Thai). Its tokens never start
+/// with a space, so each one is taken as a word of its own rather than
+/// running the whole segment together. A token starting mid-character
+/// continues the current word.
+fn starts_unspaced_word(bytes: &[u8]) -> bool {
+    let Some(c) = String::from_utf8_lossy(bytes).chars().next() else {
+        return false;
+    };
+    matches!(c,
+        '\u{0E00}'..='\u{0E7F}' // Thai
+        | '\u{3000}'..='\u{30FF}' // CJK punctuation, hiragana, katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{FF00}'..='\u{FFEF}' // Fullwidth forms
+        | '\u{20000}'..='\u{2FFFF}' // CJK extensions B and later
+    )
+}
+
+/// A word being assembled from its tokens. Raw bytes are collected because a
+/// multi-byte character can be split across tokens.
+struct PartialWord {
+    bytes: Vec<u8>,
+    start: i64,
+    end: i64,
+    tokens: Vec<Token>,
+}
+
+impl PartialWord {
+    fn finish(self) -> Option<Word> {
+        let word = String::from_utf8_lossy(&self.bytes).trim().to_string();
+        if word.is_empty() {
+            return None;
+        }
+        let n = self.tokens.len() as f32;
+        let probability = self.tokens.iter().map(|t| t.probability).sum::<f32>() / n;
+        let logprob = self.tokens.iter().map(|t| t.logprob).sum::<f32>() / n;
+        Some(Word {
+            word,
+            start: seconds(self.start),
+            end: seconds(self.end),
+            probability,
+            confidence: logprob.exp(),
+            tokens: self.tokens,
+        })
+    }
+}
+
+impl WhisperModel {
+    pub fn load(model: &Path, use_gpu: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut params = WhisperContextParameters::default();
+        params.use_gpu(use_gpu);
+        let context = WhisperContext::new_with_params(&model.to_string_lossy(), params)?;
+        let state = context.create_state()?;
+        Ok(Self { context, state })
+    }
+
+    /// Whether the model knows languages besides English; `.en` models don't.
+    pub fn is_multilingual(&self) -> bool {
+        self.context.is_multilingual()
+    }
+
+    /// Decode `samples`, with times in seconds from their start. A `language`
+    /// of `None` lets Whisper detect it. With `words`, every segment also
+    /// carries its words, timed by whisper.cpp's token timestamps and scored
+    /// by their tokens' probabilities. `on_segment` is called with each
+    /// segment as soon as it is decoded, without its words.
+    pub fn transcribe(
+        &mut self,
+        samples: &[f32],
+        language: Option<&str>,
+        translate: bool,
+        words: bool,
+        on_segment: Option<&mut dyn FnMut(&Segment)>,
+    ) -> Result<Pass, Box<dyn std::error::Error>> {
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(language.unwrap_or("auto")));
+        params.set_translate(translate);
+        params.set_token_timestamps(words);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        match on_segment {
+            Some(on_segment) => {
+                // whisper-rs wants a 'static callback, so it can't call
+                // `on_segment` itself: it sends each segment back to this
+                // thread while another one decodes.
+                let (sender, receiver) = mpsc::channel();
+                params.set_segment_callback_safe_lossy(move |data: SegmentCallbackData| {
+                    let _ = sender.send(data);
+                });
+                let state = &mut self.state;
+                thread::scope(|scope| {
+                    let decoding = scope.spawn(move || state.full(params, samples));
+                    let mut report = |data: SegmentCallbackData| {
+                        on_segment(&Segment {
+                            start: seconds(data.start_timestamp),
+                            end: seconds(data.end_timestamp),
+                            text: data.text.trim().to_string(),
+                            words: Vec::new(),
+                        })
+                    };
+                    // whisper-rs never drops the callback, so the channel
+                    // never closes; poll until decoding is done instead.
+                    while !decoding.is_finished() {
+                        if let Ok(data) = receiver.recv_timeout(POLL) {
+                            report(data);
+                        }
+                    }
+                    receiver.try_iter().for_each(report);
+                    decoding.join().expect("whisper decoding thread panicked")
+                })?;
+            }
+            None => {
+                self.state.full(params, samples)?;
+            }
+        }
+
+        let mut text = String::new();
+        let mut segments = Vec::new();
+        for i in 0..self.state.full_n_segments()? {
+            let segment_text = self.state.full_get_segment_text_lossy(i)?;
+            text.push_str(&segment_text);
+            segments.push(Segment {
+                start: seconds(self.state.full_get_segment_t0(i)?),
+                end: seconds(self.state.full_get_segment_t1(i)?),
+                text: segment_text.trim().to_string(),
+                words: if words { self.words(i)? } else { Vec::new() },
+            });
+        }
+        let language = whisper_rs::get_lang_str(self.state.full_lang_id_from_state()?);
+        Ok(Pass {
+            text: text.trim().to_string(),
+            segments,
+            language: language.map(str::to_string),
+        })
+    }
+
+    /// Run only the language identification pass, over the first 30 seconds
+    /// of `samples`: one encoder run and no decoding. Returns every language
+    /// with its probability, most likely first.
+    pub fn detect_language(
+        &mut self,
+        samples: &[f32],
+    ) -> Result<Vec<(&'static str, f32)>, Box<dyn std::error::Error>> {
+        let samples = &samples[..samples.len().min(DETECT_SAMPLES)];
+        self.state.pcm_to_mel(samples, threads())?;
+        let (_, probabilities) = self.state.lang_detect(0, threads())?;
+        let mut ranked: Vec<(&'static str, f32)> = probabilities
+            .into_iter()
+            .enumerate()
+            .filter_map(|(id, p)| Some((whisper_rs::get_lang_str(id as c_int)?, p)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+
+    /// Group a segment's text tokens into words. Whisper's vocabulary marks a
+    /// word boundary with a leading space, so every such token starts a word.
+    fn words(&self, segment: c_int) -> Result<Vec<Word>, Box<dyn std::error::Error>> {
+        // Timestamp, language and other special tokens all sort after the
+        // end-of-text token.
+        let eot = self.context.token_eot();
+        let mut words = Vec::new();
+        let mut current: Option<PartialWord> = None;
+        for i in 0..self.state.full_n_tokens(segment)? {
+            let data = self.state.full_get_token_data(segment, i)?;
+            if data.id >= eot {
+                continue;
+            }
+            let bytes = self.context.token_to_cstr(data.id)?.to_bytes();
+            let token = Token {
+                text: String::from_utf8_lossy(bytes).into_owned(),
+                probability: data.p,
+                logprob: data.plog,
+            };
+
+            match current.as_mut() {
+                Some(word) if !bytes.starts_with(b" ") && !starts_unspaced_word(bytes) => {
+                    word.bytes.extend_from_slice(bytes);
+                    word.end = data.t1;
+                    word.tokens.push(token);
+                }
+                _ => {
+                    words.extend(current.take().and_then(PartialWord::finish));
+                    current = Some(PartialWord {
+                        bytes: bytes.to_vec(),
+                        start: data.t0,
+                        end: data.t1,
+                        tokens: vec![token],
+                    });
+                }
+            }
+        }
+        words.extend(current.and_then(PartialWord::finish));
+        Ok(words)
+    }
+}